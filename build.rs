@@ -15,7 +15,7 @@ fn build_shell_completion(outdir: &Path) -> Result<(), Error> {
     let shells = Shell::value_variants();
 
     for shell in shells {
-        generate_to(*shell, &mut app, "git-ignore", &outdir)?;
+        generate_to(*shell, &mut app, "git-ignore", outdir)?;
     }
 
     Ok(())
@@ -0,0 +1,409 @@
+use crate::{cli::OnConflict, config::Config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{read_to_string, File},
+    io::Write,
+    path::{Component, Path},
+};
+
+/// A portable collection of aliases and templates, with the contents of the
+/// templates inlined so it can be shared as a single file.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Bundle {
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub templates: BTreeMap<String, BundledTemplate>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundledTemplate {
+    pub file_name: String,
+    pub contents: String,
+}
+
+impl Bundle {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut templates = BTreeMap::new();
+        for (name, file_name) in &config.templates {
+            let template = BundledTemplate {
+                file_name: file_name.clone(),
+                contents: config.read_template(file_name)?,
+            };
+            templates.insert(name.clone(), template);
+        }
+
+        Ok(Bundle {
+            aliases: config.aliases.clone().into_iter().collect(),
+            templates,
+        })
+    }
+
+    /// Reads a bundle, files ending in `.json` are parsed as JSON and
+    /// everything else as TOML.
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)
+            .with_context(|| format!("Could not read bundle {}", path.display()))?;
+
+        let bundle = if is_json(path) {
+            serde_json::from_str(&content)?
+        } else {
+            toml::from_str(&content)?
+        };
+
+        Ok(bundle)
+    }
+
+    /// Writes the bundle to `path`, or `stdout` if no path is given, as JSON
+    /// if the path ends in `.json` and TOML otherwise.
+    pub fn write(&self, path: Option<&Path>) -> Result<()> {
+        match path {
+            Some(path) => {
+                let content = if is_json(path) {
+                    serde_json::to_string_pretty(self)?
+                } else {
+                    toml::to_string_pretty(self)?
+                };
+
+                let mut file = File::create(path)?;
+                file.write_all(content.as_bytes())?;
+                eprintln!(
+                    "Exported {} aliases and {} templates to {}",
+                    self.aliases.len(),
+                    self.templates.len(),
+                    path.display().to_string().yellow()
+                );
+            }
+            None => print!("{}", toml::to_string_pretty(self)?),
+        }
+
+        Ok(())
+    }
+
+    /// Merges the bundle into `config`, writing the template files to the
+    /// templates directory. Entries that exist with different contents, or
+    /// templates whose file belongs to another template, are handled
+    /// according to `on_conflict`, identical entries are skipped.
+    ///
+    /// Nothing is imported if a template has a file name outside of the
+    /// templates directory.
+    pub fn import_into(self, config: &mut Config, on_conflict: OnConflict) -> Result<()> {
+        for (name, template) in &self.templates {
+            let path = Path::new(&template.file_name);
+            let inside = path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+            if !inside || template.file_name.is_empty() {
+                bail!(
+                    "Template {} has the file name {}, it must be a relative path inside the templates directory",
+                    name,
+                    template.file_name
+                );
+            }
+        }
+
+        let templates_dir = config.templates_dir()?;
+        let mut conflicts = 0;
+
+        for (name, aliases) in self.aliases {
+            match config.aliases.get(&name) {
+                None => println!("Imported alias {}", name.yellow()),
+                Some(existing) if existing == &aliases => continue,
+                Some(_) => {
+                    conflicts += 1;
+                    match on_conflict {
+                        OnConflict::Keep => {
+                            println!("Kept existing alias {}", name.yellow());
+                            continue;
+                        }
+                        OnConflict::Overwrite => println!("Overwrote alias {}", name.yellow()),
+                        OnConflict::Rename => {
+                            let renamed = unique(&name, |n| config.aliases.contains_key(n));
+                            println!("Imported alias {} as {}", name.yellow(), renamed.yellow());
+                            config.aliases.insert(renamed, aliases);
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            config.aliases.insert(name, aliases);
+        }
+
+        for (name, mut template) in self.templates {
+            let existing = config
+                .templates
+                .get(&name)
+                .map(|file_name| (file_name.clone(), config.read_template(file_name).ok()));
+            let owner = config
+                .templates
+                .iter()
+                .find(|(other, file_name)| **other != name && **file_name == template.file_name)
+                .map(|(other, _)| other.clone());
+            let file = templates_dir.join(&template.file_name);
+            let file_conflict = owner.is_some()
+                || file.exists() && read_to_string(&file).ok().as_ref() != Some(&template.contents);
+
+            let mut name = name;
+            match existing {
+                Some((file_name, Some(contents)))
+                    if file_name == template.file_name && contents == template.contents =>
+                {
+                    continue
+                }
+                None if !file_conflict => println!("Imported template {}", name.blue()),
+                _ => {
+                    conflicts += 1;
+                    match on_conflict {
+                        OnConflict::Keep => {
+                            println!("Kept existing template {}", name.blue());
+                            continue;
+                        }
+                        OnConflict::Overwrite => match owner {
+                            // Never overwrite the file of another template
+                            Some(owner) => {
+                                template.file_name = unique_file_name(&template.file_name, |f| {
+                                    templates_dir.join(f).exists()
+                                });
+                                println!(
+                                    "Overwrote template {} in {}, as its file belongs to {}",
+                                    name.blue(),
+                                    template.file_name.yellow(),
+                                    owner.blue()
+                                );
+                            }
+                            None => println!("Overwrote template {}", name.blue()),
+                        },
+                        OnConflict::Rename => {
+                            let renamed = unique(&name, |n| config.templates.contains_key(n));
+                            template.file_name = unique_file_name(&template.file_name, |f| {
+                                templates_dir.join(f).exists()
+                            });
+                            println!(
+                                "Imported template {} as {} in {}",
+                                name.blue(),
+                                renamed.blue(),
+                                template.file_name.yellow()
+                            );
+                            name = renamed;
+                        }
+                    }
+                }
+            }
+
+            let mut file = File::create(templates_dir.join(&template.file_name))?;
+            file.write_all(template.contents.as_bytes())?;
+            config.templates.insert(name, template.file_name);
+        }
+
+        if conflicts > 0 {
+            eprintln!(
+                "{}: {} entries conflicted with existing aliases or templates",
+                "Info".bold().green(),
+                conflicts,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Appends `-1`, `-2` and so on to `name` until it is no longer `taken`.
+fn unique<F: Fn(&str) -> bool>(name: &str, taken: F) -> String {
+    (1..)
+        .map(|i| format!("{}-{}", name, i))
+        .find(|candidate| !taken(candidate))
+        .expect("There is always a free name")
+}
+
+/// Like `unique`, but keeps the extension of the file name.
+pub fn unique_file_name<F: Fn(&str) -> bool>(file_name: &str, taken: F) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().map_or_else(
+        || file_name.to_string(),
+        |s| s.to_string_lossy().into_owned(),
+    );
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|i| format!("{}-{}{}", stem, i, extension))
+        .find(|candidate| !taken(candidate))
+        .expect("There is always a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bundle::{Bundle, BundledTemplate},
+        cli::OnConflict,
+        config::Config,
+        testing::TempDir,
+    };
+    use std::fs::read_to_string;
+
+    /// A config in `dir` with a `web` alias, unless `web` is empty, and the
+    /// given templates, as name, file name and contents.
+    fn config_with(dir: &TempDir, web: &[&str], templates: &[(&str, &str, &str)]) -> Config {
+        let mut config = dir.config();
+        if !web.is_empty() {
+            let web = web.iter().map(|name| name.to_string()).collect();
+            config.aliases.insert("web".to_string(), web);
+        }
+        for (name, file_name, contents) in templates {
+            dir.write(&format!("templates/{}", file_name), contents);
+            config
+                .templates
+                .insert(name.to_string(), file_name.to_string());
+        }
+
+        config
+    }
+
+    /// Exports `source` through TOML and imports it into `target`, returning
+    /// the contents of every template file in `target` afterwards.
+    fn round_trip(
+        source: &Config,
+        target: &mut Config,
+        on_conflict: OnConflict,
+    ) -> Vec<(String, String)> {
+        let exported = toml::to_string_pretty(&Bundle::from_config(source).unwrap()).unwrap();
+        let bundle: Bundle = toml::from_str(&exported).unwrap();
+        bundle.import_into(target, on_conflict).unwrap();
+
+        let mut files: Vec<(String, String)> = target
+            .templates
+            .iter()
+            .map(|(name, file_name)| (name.clone(), target.read_template(file_name).unwrap()))
+            .collect();
+        files.sort_unstable();
+        files
+    }
+
+    #[test]
+    fn round_trips_into_empty_config() {
+        let source_dir = TempDir::new("bundle-source");
+        let source = config_with(
+            &source_dir,
+            &["node", "macos"],
+            &[("docs", "docs.txt", "/docs\n")],
+        );
+        let target_dir = TempDir::new("bundle-empty");
+        let mut target = config_with(&target_dir, &[], &[]);
+
+        let files = round_trip(&source, &mut target, OnConflict::Keep);
+
+        assert_eq!(target.aliases, source.aliases);
+        assert_eq!(target.templates, source.templates);
+        assert_eq!(files, vec![("docs".to_string(), "/docs\n".to_string())]);
+    }
+
+    #[test]
+    fn keeps_existing_entries() {
+        let source_dir = TempDir::new("bundle-keep-source");
+        let source = config_with(&source_dir, &["node"], &[("docs", "docs.txt", "/new\n")]);
+        let target_dir = TempDir::new("bundle-keep");
+        let mut target = config_with(&target_dir, &["rust"], &[("docs", "docs.txt", "/old\n")]);
+
+        let files = round_trip(&source, &mut target, OnConflict::Keep);
+
+        assert_eq!(target.aliases["web"], vec!["rust"]);
+        assert_eq!(files, vec![("docs".to_string(), "/old\n".to_string())]);
+    }
+
+    #[test]
+    fn overwrites_existing_entries() {
+        let source_dir = TempDir::new("bundle-overwrite-source");
+        let source = config_with(&source_dir, &["node"], &[("docs", "docs.txt", "/new\n")]);
+        let target_dir = TempDir::new("bundle-overwrite");
+        let mut target = config_with(&target_dir, &["rust"], &[("docs", "docs.txt", "/old\n")]);
+
+        let files = round_trip(&source, &mut target, OnConflict::Overwrite);
+
+        assert_eq!(target.aliases["web"], vec!["node"]);
+        assert_eq!(files, vec![("docs".to_string(), "/new\n".to_string())]);
+    }
+
+    #[test]
+    fn renames_conflicting_entries() {
+        let source_dir = TempDir::new("bundle-rename-source");
+        let source = config_with(&source_dir, &["node"], &[("docs", "docs.txt", "/new\n")]);
+        let target_dir = TempDir::new("bundle-rename");
+        let mut target = config_with(&target_dir, &["rust"], &[("docs", "docs.txt", "/old\n")]);
+
+        let files = round_trip(&source, &mut target, OnConflict::Rename);
+
+        assert_eq!(target.aliases["web"], vec!["rust"]);
+        assert_eq!(target.aliases["web-1"], vec!["node"]);
+        assert_eq!(target.templates["docs-1"], "docs-1.txt");
+        assert_eq!(
+            files,
+            vec![
+                ("docs".to_string(), "/old\n".to_string()),
+                ("docs-1".to_string(), "/new\n".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_overwrite_files_of_other_templates() {
+        let source_dir = TempDir::new("bundle-shared-source");
+        let source = config_with(&source_dir, &[], &[("b", "shared.txt", "/b\n")]);
+        let target_dir = TempDir::new("bundle-shared");
+        let mut target = config_with(&target_dir, &[], &[("a", "shared.txt", "/a\n")]);
+
+        let files = round_trip(&source, &mut target, OnConflict::Overwrite);
+
+        assert_eq!(target.templates["b"], "shared-1.txt");
+        assert_eq!(
+            files,
+            vec![
+                ("a".to_string(), "/a\n".to_string()),
+                ("b".to_string(), "/b\n".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_file_names_outside_the_templates_directory() {
+        let target_dir = TempDir::new("bundle-traversal");
+        let mut target = config_with(&target_dir, &[], &[]);
+        let templates_dir = target.templates_dir().unwrap();
+
+        for file_name in ["../escaped.txt", "/tmp/escaped.txt", ""] {
+            let mut bundle = Bundle::default();
+            bundle.templates.insert(
+                "a".to_string(),
+                BundledTemplate {
+                    file_name: "a.txt".to_string(),
+                    contents: "/a\n".to_string(),
+                },
+            );
+            bundle.templates.insert(
+                "b".to_string(),
+                BundledTemplate {
+                    file_name: file_name.to_string(),
+                    contents: "/b\n".to_string(),
+                },
+            );
+
+            assert!(bundle
+                .import_into(&mut target, OnConflict::Overwrite)
+                .is_err());
+        }
+        let written = read_to_string(templates_dir.join("a.txt")).is_ok();
+        let escaped = templates_dir.join("../escaped.txt").exists();
+
+        assert!(target.templates.is_empty());
+        assert!(!written && !escaped);
+    }
+}
@@ -10,7 +10,6 @@ use std::io;
     author,
     global_setting = AppSettings::DeriveDisplayOrder,
 )]
-#[clap(args_conflicts_with_subcommands = true)]
 #[allow(clippy::upper_case_acronyms)]
 /// Quickly and easily add templates to .gitignore
 pub struct CLI {
@@ -23,9 +22,36 @@ pub struct CLI {
     /// Ignore all user defined aliases and templates
     #[clap(short, long)]
     pub simple: bool,
+    /// Do not add the default templates from the config
+    #[clap(long)]
+    pub no_defaults: bool,
     /// Autodetect templates based on the existing files
     #[clap(short, long)]
     pub auto: bool,
+    /// How many levels of directories to scan when autodetecting
+    #[clap(long, value_name = "DEPTH", requires = "auto")]
+    pub depth: Option<usize>,
+    /// Also autodetect operating system and editor templates from the environment
+    #[clap(long, requires = "auto")]
+    pub env: bool,
+    /// Autodetect templates from the files tracked by git instead of the working tree
+    #[clap(long, requires = "auto")]
+    pub tracked: bool,
+    /// Only autodetect templates with at least this confidence, from 0 to 1
+    #[clap(long, value_name = "CONFIDENCE", requires = "auto")]
+    pub threshold: Option<f64>,
+    /// Ask whether to add autodetected templates with a low confidence
+    #[clap(short, long, requires = "auto")]
+    pub interactive: bool,
+    /// Set a variable used by custom templates
+    #[clap(long, value_name = "KEY=VALUE", parse(try_from_str = parse_key_value), multiple_occurrences = true)]
+    pub set: Vec<(String, String)>,
+    /// Path to the config file
+    #[clap(long, global = true, env = "GIT_IGNORE_CONFIG", value_name = "FILE")]
+    pub config: Option<std::path::PathBuf>,
+    /// Path to the cache directory
+    #[clap(long, global = true, env = "GIT_IGNORE_CACHE_DIR", value_name = "DIR")]
+    pub cache_dir: Option<std::path::PathBuf>,
     /// Configuration management
     #[clap(subcommand)]
     pub cmd: Option<Cmds>,
@@ -41,6 +67,68 @@ pub enum Cmds {
     /// Manage local templates
     #[clap(subcommand)]
     Template(TemplateCmd),
+    /// Manage patches to templates from gitignore.io
+    #[clap(subcommand)]
+    Patch(PatchCmd),
+    /// Inspect the configuration
+    #[clap(subcommand)]
+    Config(ConfigCmd),
+    /// Explain which files and matchers `--auto` detects templates from
+    Detect {
+        /// How many levels of directories to scan
+        #[clap(long, value_name = "DEPTH")]
+        depth: Option<usize>,
+        /// Also detect operating system and editor templates from the environment
+        #[clap(long)]
+        env: bool,
+        /// Detect templates from the files tracked by git instead of the working tree
+        #[clap(long)]
+        tracked: bool,
+        /// Only detect templates with at least this confidence, from 0 to 1
+        #[clap(long, value_name = "CONFIDENCE")]
+        threshold: Option<f64>,
+    },
+    /// Pick templates interactively, with fuzzy search and a preview
+    Pick {
+        /// Write the picked templates to the managed section of .gitignore
+        #[clap(short, long)]
+        write: bool,
+        /// Don't add the default templates from the config
+        #[clap(long)]
+        no_defaults: bool,
+    },
+    /// Check that every detector adds a template or alias that exists
+    Audit,
+    /// Write a .gitignore with the detected templates to every subproject
+    Monorepo {
+        /// How many levels of directories to scan
+        #[clap(long, value_name = "DEPTH")]
+        depth: Option<usize>,
+        /// Detect templates from the files tracked by git instead of the working tree
+        #[clap(long)]
+        tracked: bool,
+        /// Don't add the default templates from the config
+        #[clap(long)]
+        no_defaults: bool,
+        /// Only print the plan, without writing anything
+        #[clap(long)]
+        dry_run: bool,
+        /// Write the files without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Export aliases and templates to a single file
+    Export {
+        /// File to write to, as JSON if it ends in `.json` and TOML otherwise
+        file: Option<std::path::PathBuf>,
+    },
+    /// Import aliases and templates exported with `export`
+    Import {
+        file: std::path::PathBuf,
+        /// What to do with aliases and templates that already exist
+        #[clap(long, arg_enum, default_value = "keep")]
+        on_conflict: OnConflict,
+    },
     /// Initialize user configuration
     Init {
         /// Forcefully create config, possibly overwrite existing
@@ -73,9 +161,83 @@ pub enum TemplateCmd {
     #[clap(visible_alias = "ls")]
     List,
     /// Add a new template
-    Add { name: String, file_name: String },
+    Add {
+        name: String,
+        file_name: String,
+        /// Overwrite the template file if it already exists
+        #[clap(long)]
+        force: bool,
+    },
     /// Remove a template
     #[clap(visible_alias = "rm")]
+    Remove {
+        name: String,
+        /// Delete the template file as well
+        #[clap(long, conflicts_with = "archive")]
+        delete: bool,
+        /// Move the template file to the archive directory
+        #[clap(long)]
+        archive: bool,
+    },
+    /// Find orphaned template files and templates with missing files
+    Check,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{}`", s))
+}
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug)]
+pub enum OnConflict {
+    /// Keep the existing entry
+    Keep,
+    /// Replace the existing entry
+    Overwrite,
+    /// Import the entry under a new name
+    Rename,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCmd {
+    /// Show the user config merged with the project config
+    Show {
+        /// Show which file each entry comes from
+        #[clap(long)]
+        origin: bool,
+    },
+    /// Print the value of a key, e.g. `server` or `aliases.node`
+    Get { key: String },
+    /// Set the value of a key in the user config
+    Set {
+        key: String,
+        /// A TOML value, anything that is not valid TOML is used as a string
+        value: String,
+    },
+    /// Open the user config in $VISUAL or $EDITOR
+    Edit,
+    /// Check the user and project config for errors
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PatchCmd {
+    /// List available patches
+    #[clap(visible_alias = "ls")]
+    List,
+    /// Add lines to remove from or append to a template
+    Add {
+        name: String,
+        /// Line to remove from the template
+        #[clap(long, multiple_occurrences = true)]
+        remove: Vec<String>,
+        /// Line to append to the template
+        #[clap(long, multiple_occurrences = true, allow_hyphen_values = true)]
+        append: Vec<String>,
+    },
+    /// Remove all patches for a template
+    #[clap(visible_alias = "rm")]
     Remove { name: String },
 }
 
@@ -1,15 +1,51 @@
-use crate::ignore::{project_dirs, Type};
-use anyhow::{Context, Result};
+use crate::{
+    bundle::{unique_file_name, Bundle},
+    cli::OnConflict,
+    detector::DetectionConfig,
+    ignore::{project_dirs, Type},
+    registry::Registry,
+    template::Patch,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs::{read_to_string, File},
+    collections::{HashMap, HashSet},
+    env::{current_dir, var},
+    fs::{copy, create_dir_all, read_dir, read_to_string, remove_file, rename, File},
     io::Write,
     path::{Path, PathBuf},
+    process::Command,
 };
 
-fn config_file() -> PathBuf {
+pub const PROJECT_FILE: &str = ".git-ignore.toml";
+
+/// The current version of the config schema.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Migrations from each version of the config schema to the next, the first
+/// entry migrates from version 1 to 2.
+const MIGRATIONS: &[fn(&mut toml::value::Table)] = &[
+    // Version 2 adds the version field and makes every section optional
+    |_| {},
+];
+
+/// The keys that are allowed at the top level of a config file.
+const KEYS: &[&str] = &[
+    "version",
+    "aliases",
+    "templates",
+    "variables",
+    "patches",
+    "server",
+    "defaults",
+    "registries",
+    "detection",
+];
+
+/// The default location of the user config, in the config directory for the
+/// current platform.
+pub fn default_config_file() -> PathBuf {
     let dirs = project_dirs();
 
     [
@@ -22,55 +58,446 @@ fn config_file() -> PathBuf {
     .collect()
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Finds the project config in `dir` or the closest parent directory, up to
+/// the root of the repository.
+fn project_file(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let file = dir.join(PROJECT_FILE);
+        if file.is_file() {
+            return Some(file);
+        }
+
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct Config {
     #[serde(skip)]
     pub path: PathBuf,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub defaults: Vec<String>,
+    #[serde(default)]
     pub aliases: HashMap<String, Vec<String>>,
+    #[serde(default)]
     pub templates: HashMap<String, String>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub patches: HashMap<String, Patch>,
+    #[serde(default)]
+    pub registries: HashMap<String, Registry>,
+    #[serde(default)]
+    pub detection: DetectionConfig,
 }
 
 impl Config {
-    pub fn create(force: bool) -> Result<()> {
-        let config_file = config_file();
+    pub fn create(config_file: PathBuf, force: bool) -> Result<()> {
         Config::create_dir(
             config_file
                 .parent()
                 .context("No parent dir for the config_file")?,
         );
 
-        if config_file.exists() && !force {
-            println!("{}: config already exist", "INFO".bold().blue());
-            return Ok(());
-        }
+        if config_file.exists() {
+            if let Err(e) = Config::read(&config_file) {
+                if !force {
+                    return Err(e.context(
+                        "Refusing to touch a broken config, pass `--force` to replace it",
+                    ));
+                }
 
-        if config_file.exists() && force {
-            eprintln!("{}: overwriting existing config file", "WARN".bold().red());
+                let backup = config_file.with_extension("toml.bak");
+                rename(&config_file, &backup)?;
+                eprintln!(
+                    "{}: moved broken config file to {}",
+                    "WARN".bold().red(),
+                    backup.display()
+                );
+            } else if !force {
+                println!("{}: config already exist", "INFO".bold().blue());
+                return Ok(());
+            } else {
+                eprintln!("{}: overwriting existing config file", "WARN".bold().red());
+            }
         }
 
         let config = Config::new(config_file);
         config.write()
     }
 
-    pub fn from_dir() -> Option<Self> {
-        let config_file = config_file();
-        if config_file.exists() {
-            let file = Path::new(&config_file);
-            let file = match read_to_string(file) {
-                Ok(content) => content,
-                Err(_) => return None,
-            };
-
-            match toml::from_str::<Config>(&file).as_mut() {
-                Ok(config) => {
-                    config.path = config_file;
-                    Some(config.clone())
-                }
-                Err(_) => None,
+    /// Reads the config file if it exists. Errors when reading or parsing the
+    /// file are reported instead of treating the config as missing, so that a
+    /// typo does not silently disable aliases and templates.
+    ///
+    /// Configs with an older schema are upgraded in place, keeping a backup of
+    /// the original file.
+    pub fn from_dir(config_file: &Path) -> Result<Option<Self>> {
+        if !config_file.exists() {
+            return Ok(None);
+        }
+
+        let (config, version) = Config::read_versioned(config_file)?;
+        if version < CONFIG_VERSION {
+            let backup = config_file.with_extension(format!("toml.v{}.bak", version));
+            copy(config_file, &backup)?;
+            config.write()?;
+            eprintln!(
+                "{}: migrated config from version {} to {}, the old config is saved as {}",
+                "Info".bold().green(),
+                version,
+                CONFIG_VERSION,
+                backup.display()
+            );
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Reads the project config, `.git-ignore.toml`, in the current directory
+    /// or the closest parent directory up to the root of the repository.
+    /// Template paths in the project config are relative to the file.
+    pub fn from_project() -> Result<Option<Self>> {
+        let project_file = match project_file(&current_dir()?) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+
+        Config::read_relative(&project_file).map(Some)
+    }
+
+    /// Reads a config where the template paths are relative to the file,
+    /// like the project config or the manifest of a registry.
+    pub fn read_relative(path: &Path) -> Result<Self> {
+        let mut config = Config::read(path)?;
+        let dir = path
+            .parent()
+            .context("Could not get parent directory of config")?;
+        for template in config.templates.values_mut() {
+            *template = dir.join(&template).to_string_lossy().into_owned();
+        }
+        config.resolve_registries(dir);
+
+        Ok(config)
+    }
+
+    /// Makes the paths of local registries relative to `dir`, the directory
+    /// of the config declaring them.
+    pub fn resolve_registries(&mut self, dir: &Path) {
+        for registry in self.registries.values_mut() {
+            if let Some(path) = &mut registry.path {
+                *path = dir.join(&path);
+            }
+        }
+    }
+
+    /// Merges `other` into this config, entries in `other` take precedence and
+    /// default templates from both are combined.
+    pub fn merge(&mut self, other: &Config) {
+        self.version = self.version.max(other.version);
+        self.aliases.extend(other.aliases.clone());
+        self.templates.extend(other.templates.clone());
+        self.variables.extend(other.variables.clone());
+        self.patches.extend(other.patches.clone());
+        self.registries.extend(other.registries.clone());
+        self.detection.merge(&other.detection);
+        for default in &other.defaults {
+            if !self.defaults.contains(default) {
+                self.defaults.push(default.clone());
+            }
+        }
+        if other.server.is_some() {
+            self.server = other.server.clone();
+        }
+    }
+
+    /// Sets a dotted `key` to `value`, where `value` is parsed as TOML and
+    /// otherwise used as a string. The key must exist in the config format
+    /// and the result must still be a valid config. Unknown entries in the
+    /// file are kept as they are.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let section = key.split('.').next().unwrap_or_default();
+        if section == "version" {
+            bail!("The version is managed by git ignore and cannot be set");
+        }
+        if !KEYS.contains(&section) {
+            bail!("Unknown key {}, expected one of {}", key, KEYS.join(", "));
+        }
+
+        let value = toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+        let mut config = toml::Value::try_from(&*self)?;
+        let (parents, last) = match key.rsplit_once('.') {
+            Some((parents, last)) => (parents.split('.').collect(), last),
+            None => (vec![], key),
+        };
+
+        let mut table = config
+            .as_table_mut()
+            .context("The config is not a table, this should never happen")?;
+        for part in parents {
+            table = table
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+                .as_table_mut()
+                .with_context(|| format!("{} is not a table", part))?;
+        }
+        table.insert(last.to_string(), value.clone());
+
+        let mut config: Config = config
+            .try_into()
+            .with_context(|| format!("Invalid value {} for {}", value, key))?;
+        config.path = self.path.clone();
+
+        // Keys that are dropped when serializing again are not part of the
+        // format, unless the value was emptied
+        let mut written = toml::Value::try_from(&config)?;
+        let resolves = key
+            .split('.')
+            .try_fold(&written, |value, part| value.get(part))
+            .is_some();
+        let emptied = match &value {
+            toml::Value::Array(array) => array.is_empty(),
+            toml::Value::Table(table) => table.is_empty(),
+            _ => false,
+        };
+        if !resolves && !emptied {
+            bail!("Unknown key {}", key);
+        }
+
+        if let Some(table) = written.as_table_mut() {
+            table.extend(self.unknown_entries());
+        }
+        *self = config;
+
+        println!("Set {} to {}", key.blue(), value);
+        let mut file = File::create(&self.path)?;
+        file.write_all(toml::to_string_pretty(&written)?.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// The top level entries of the config file that are not part of the
+    /// format, empty if the file can not be read.
+    fn unknown_entries(&self) -> toml::value::Table {
+        let table = read_to_string(&self.path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::value::Table>(&content).ok())
+            .unwrap_or_default();
+
+        table
+            .into_iter()
+            .filter(|(key, _)| !KEYS.contains(&key.as_str()))
+            .collect()
+    }
+
+    /// Opens the config file in `$VISUAL` or `$EDITOR`, falling back to `vi`,
+    /// and validates it afterwards.
+    pub fn edit(config_file: &Path, cache_dir: &Path) -> Result<()> {
+        if !config_file.exists() {
+            bail!("No config found, run `git ignore init` to create it.");
+        }
+
+        let editor = var("VISUAL")
+            .or_else(|_| var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut args = editor.split_whitespace();
+        let program = args.next().context("$VISUAL or $EDITOR is empty")?;
+
+        let status = Command::new(program)
+            .args(args)
+            .arg(config_file)
+            .status()
+            .with_context(|| format!("Could not start editor {}", editor))?;
+        if !status.success() {
+            bail!("Editor {} exited with {}", editor, status);
+        }
+
+        Config::validate(config_file, cache_dir)
+    }
+
+    /// Checks that the user config and project config can be parsed, only
+    /// contain known keys, that every template file exists and that the
+    /// manifest of every registry can be read, git registries are read from
+    /// their clone in `cache_dir`.
+    pub fn validate(config_file: &Path, cache_dir: &Path) -> Result<()> {
+        let mut files = vec![config_file.to_path_buf()];
+        files.extend(project_file(&current_dir()?));
+
+        let mut valid = true;
+        for file in files.iter().filter(|file| file.exists()) {
+            let problems = Config::problems(file, cache_dir);
+            if problems.is_empty() {
+                println!("{}: {}", file.display(), "valid".green());
+                continue;
             }
+
+            valid = false;
+            println!("{}:", file.display());
+            for problem in problems {
+                println!("  {}", problem.red());
+            }
+        }
+
+        if !valid {
+            bail!("Invalid config");
+        }
+
+        Ok(())
+    }
+
+    fn problems(file: &Path, cache_dir: &Path) -> Vec<String> {
+        let mut config = match Config::read(file) {
+            Ok(config) => config,
+            Err(e) => return vec![format!("{:#}", e)],
+        };
+        if let Some(dir) = file.parent() {
+            config.resolve_registries(dir);
+        }
+
+        let mut problems = Vec::new();
+        if let Ok(toml::Value::Table(table)) = read_to_string(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(toml::from_str(&content)?))
+        {
+            let mut unknown: Vec<&String> = table
+                .keys()
+                .filter(|key| !KEYS.contains(&key.as_str()))
+                .collect();
+            unknown.sort_unstable();
+            problems.extend(
+                unknown
+                    .into_iter()
+                    .map(|key| format!("unknown key {}", key)),
+            );
+        }
+
+        let dir = file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        let templates_dir = if file.ends_with(PROJECT_FILE) {
+            dir
         } else {
-            None
+            dir.join("templates")
+        };
+
+        let mut templates: Vec<(&String, &String)> = config.templates.iter().collect();
+        templates.sort_unstable();
+        for (name, path) in templates {
+            if !templates_dir.join(path).is_file() {
+                problems.push(format!("template {} refers to missing file {}", name, path));
+            }
+        }
+
+        let mut aliases: Vec<&String> = config
+            .aliases
+            .iter()
+            .filter(|(_, templates)| templates.is_empty())
+            .map(|(name, _)| name)
+            .collect();
+        aliases.sort_unstable();
+        problems.extend(
+            aliases
+                .into_iter()
+                .map(|name| format!("alias {} is empty", name)),
+        );
+
+        let mut registries: Vec<(&String, &Registry)> = config.registries.iter().collect();
+        registries.sort_unstable_by_key(|(name, _)| *name);
+        for (name, registry) in registries {
+            match registry.load(name, cache_dir) {
+                Ok(Some(_)) => {}
+                Ok(None) => problems.push(format!(
+                    "registry {} has not been fetched, pass '-u' to fetch it",
+                    name
+                )),
+                Err(e) => problems.push(format!("{:#}", e)),
+            }
         }
+        problems.extend(config.detection.problems());
+
+        problems
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        Config::read_versioned(path).map(|(config, _)| config)
+    }
+
+    /// Reads a config file, migrating it to the current schema if it is
+    /// older. Returns the config and the version of the file.
+    fn read_versioned(path: &Path) -> Result<(Self, u32)> {
+        let content = read_to_string(path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        // The location is moved from the end of the message to the file name
+        let parse_error = |e: toml::de::Error| match e.line_col() {
+            Some((line, col)) => {
+                let message = e.to_string();
+                let location = format!(" at line {} column {}", line + 1, col + 1);
+                anyhow!(
+                    "Could not parse config file {}:{}:{}\n{}",
+                    path.display(),
+                    line + 1,
+                    col + 1,
+                    message.strip_suffix(&location).unwrap_or(&message)
+                )
+            }
+            None => anyhow!("Could not parse config file {}\n{}", path.display(), e),
+        };
+
+        let mut table: toml::value::Table = toml::from_str(&content).map_err(parse_error)?;
+        let version = match table.get("version") {
+            None => 1,
+            Some(toml::Value::Integer(version)) if *version >= 1 => *version as u32,
+            Some(version) => bail!(
+                "Invalid version {} in config file {}",
+                version,
+                path.display()
+            ),
+        };
+
+        if version > CONFIG_VERSION {
+            bail!(
+                "Config file {} has version {}, but this version of git-ignore only supports {}",
+                path.display(),
+                version,
+                CONFIG_VERSION
+            );
+        }
+
+        // Deserialize directly from the file when possible to keep the
+        // location in error messages
+        let mut config = if version == CONFIG_VERSION {
+            toml::from_str::<Config>(&content).map_err(parse_error)?
+        } else {
+            Config::migrate(&mut table, version);
+            toml::Value::Table(table)
+                .try_into::<Config>()
+                .with_context(|| format!("Could not migrate config file {}", path.display()))?
+        };
+        config.path = path.to_path_buf();
+
+        Ok((config, version))
+    }
+
+    /// Upgrades a config from an older schema version to the current one, one
+    /// version at a time.
+    fn migrate(table: &mut toml::value::Table, from: u32) {
+        for version in from..CONFIG_VERSION {
+            MIGRATIONS[version as usize - 1](table);
+        }
+        table.insert(
+            "version".into(),
+            toml::Value::Integer(CONFIG_VERSION.into()),
+        );
     }
 
     pub fn list_aliases(&self) {
@@ -110,13 +537,23 @@ impl Config {
         }
     }
 
-    pub fn add_template(&mut self, name: String, file_name: String) -> Result<()> {
-        let file = self
-            .path
-            .parent()
-            .context("Could not get parent directory of config file")?
-            .join("templates")
-            .join(&file_name);
+    pub fn add_template(&mut self, name: String, file_name: String, force: bool) -> Result<()> {
+        let file = self.templates_dir()?.join(&file_name);
+
+        if file.exists() && !force {
+            bail!(
+                "Template file {} already exists, pass `--force` to overwrite it",
+                file.display()
+            );
+        }
+
+        if file.exists() && force {
+            eprintln!(
+                "{}: overwriting existing template file {}",
+                "WARN".bold().red(),
+                file.display()
+            );
+        }
 
         println!(
             "Created template {} at {}",
@@ -131,11 +568,149 @@ impl Config {
         self.write()
     }
 
-    pub fn remove_template(&mut self, name: &str) -> Result<()> {
-        if self.templates.remove(name).is_some() {
-            println!("Removed template {}", name.blue());
+    pub fn remove_template(&mut self, name: &str, delete: bool, archive: bool) -> Result<()> {
+        let file_name = match self.templates.remove(name) {
+            Some(file_name) => file_name,
+            None => {
+                println!("No template named {} found", name.blue());
+                return Ok(());
+            }
+        };
+
+        println!("Removed template {}", name.blue());
+
+        let file = self.templates_dir()?.join(&file_name);
+        if !file.exists() {
+            if delete || archive {
+                eprintln!(
+                    "{}: template file {} does not exist",
+                    "WARN".bold().red(),
+                    file.display()
+                );
+            }
+        } else if delete {
+            remove_file(&file)?;
+            println!("Deleted {}", file.display().to_string().yellow());
+        } else if archive {
+            let mut target = self.templates_dir()?.join(".archive").join(&file_name);
+            if target.exists() {
+                let name = target.file_name().unwrap_or_default().to_string_lossy();
+                let name = unique_file_name(&name, |name| target.with_file_name(name).exists());
+                target = target.with_file_name(name);
+            }
+            create_dir_all(
+                target
+                    .parent()
+                    .context("Could not get the archive directory")?,
+            )?;
+            rename(&file, &target)?;
+            println!(
+                "Archived {} to {}",
+                file.display().to_string().yellow(),
+                target.display().to_string().yellow()
+            );
+        }
+
+        self.write()
+    }
+
+    /// Compares the files in the templates directory with the templates that
+    /// are registered in the config, reporting files that no template refers to
+    /// and templates whose file is missing.
+    pub fn check_templates(&self) {
+        let (orphaned, missing) = match self.template_mismatches() {
+            Ok(mismatches) => mismatches,
+            Err(e) => return eprintln!("{}: {}", "ERROR".bold().red(), e),
+        };
+
+        if orphaned.is_empty() && missing.is_empty() {
+            return println!("{}", "All templates are in order".green());
+        }
+
+        if !orphaned.is_empty() {
+            println!("{}", "Files not registered as templates:".bold().green());
+            for file_name in orphaned {
+                println!("  {}", file_name.yellow());
+            }
+        }
+
+        if !missing.is_empty() {
+            println!("{}", "Templates with missing files:".bold().green());
+            for name in missing {
+                println!("  {} => {:?}", name.blue(), self.templates[name]);
+            }
+        }
+    }
+
+    /// The files in the templates directory that no template refers to, and
+    /// the names of the templates whose file is missing, both sorted.
+    fn template_mismatches(&self) -> Result<(Vec<String>, Vec<&String>)> {
+        let dir = self.templates_dir()?;
+
+        let registered: HashSet<&str> = self.templates.values().map(String::as_str).collect();
+        let mut orphaned: Vec<String> = read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_file())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| !registered.contains(name.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        orphaned.sort_unstable();
+
+        let mut missing: Vec<&String> = self
+            .templates
+            .iter()
+            .filter(|(_, file_name)| !dir.join(file_name).is_file())
+            .map(|(name, _)| name)
+            .collect();
+        missing.sort_unstable();
+
+        Ok((orphaned, missing))
+    }
+
+    pub fn list_patches(&self) {
+        if self.patches.is_empty() {
+            return println!("{}", "No patches defined".blue());
+        }
+
+        println!("{}", "Available patches:".bold().green());
+        for (name, patch) in &self.patches {
+            println!(
+                "{} => remove {:?}, append {:?}",
+                name.magenta(),
+                patch.remove,
+                patch.append
+            );
+        }
+    }
+
+    pub fn add_patch(
+        &mut self,
+        name: String,
+        remove: Vec<String>,
+        append: Vec<String>,
+    ) -> Result<()> {
+        let patch = self.patches.entry(name.clone()).or_default();
+        patch.remove.extend(remove);
+        patch.append.extend(append);
+
+        println!(
+            "Patched {}: remove {:?}, append {:?}",
+            name.magenta(),
+            patch.remove,
+            patch.append
+        );
+        self.write()
+    }
+
+    pub fn remove_patch(&mut self, name: &str) -> Result<()> {
+        if self.patches.remove(name).is_some() {
+            println!("Removed patch for {}", name.magenta());
         } else {
-            println!("No template named {} found", name.blue());
+            println!("No patch for {} found", name.magenta());
         }
         self.write()
     }
@@ -143,29 +718,51 @@ impl Config {
     pub fn names(&self) -> Vec<Type> {
         let aliases = self.aliases.keys();
         let templates = self.templates.keys();
+        let patches = self.patches.keys();
 
         let mut res: Vec<_> = aliases.cloned().map(Type::Alias).collect();
         res.extend(templates.cloned().map(Type::Template));
+        res.extend(patches.cloned().map(Type::Patched));
         res.sort_unstable();
 
         res
     }
 
-    pub fn read_template(path: &str) -> Result<String> {
-        let dir = project_dirs().config_dir().join("templates").join(path);
-        let content = read_to_string(dir)?;
+    /// Reads a template relative to the templates directory next to the
+    /// config file, absolute paths are read as is.
+    pub fn read_template(&self, path: &str) -> Result<String> {
+        let file = self.templates_dir()?.join(path);
+        let content = read_to_string(&file)
+            .with_context(|| format!("Could not read template {}", file.display()))?;
 
         Ok(content)
     }
 
     fn new(path: PathBuf) -> Self {
         Self {
-            aliases: HashMap::default(),
-            templates: HashMap::default(),
             path,
+            version: CONFIG_VERSION,
+            ..Default::default()
         }
     }
 
+    pub fn export(&self, file: Option<PathBuf>) -> Result<()> {
+        Bundle::from_config(self)?.write(file.as_deref())
+    }
+
+    pub fn import(&mut self, file: PathBuf, on_conflict: OnConflict) -> Result<()> {
+        Bundle::read(&file)?.import_into(self, on_conflict)?;
+        self.write()
+    }
+
+    pub fn templates_dir(&self) -> Result<PathBuf> {
+        Ok(self
+            .path
+            .parent()
+            .context("Could not get parent directory of config file")?
+            .join("templates"))
+    }
+
     fn write(&self) -> Result<()> {
         let mut file = File::create(&self.path)?;
         file.write_all(toml::to_string_pretty(self)?.as_bytes())?;
@@ -175,7 +772,7 @@ impl Config {
 
     fn create_dir(path: &Path) {
         if !path.exists() {
-            std::fs::create_dir_all(&path).expect("Could not create config directory");
+            std::fs::create_dir_all(path).expect("Could not create config directory");
         }
 
         let path = path.join("templates");
@@ -184,3 +781,396 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{project_file, Config, CONFIG_VERSION, PROJECT_FILE},
+        testing::TempDir,
+    };
+    use std::fs::read_to_string;
+
+    #[test]
+    fn migrates_version_1_config() {
+        let mut table = toml::from_str(
+            r#"
+            [aliases]
+            node = ["node", "nextjs"]
+
+            [templates]
+            docs = "docs.txt"
+            "#,
+        )
+        .unwrap();
+        Config::migrate(&mut table, 1);
+
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.aliases["node"], vec!["node", "nextjs"]);
+        assert_eq!(config.templates["docs"], "docs.txt");
+    }
+
+    #[test]
+    fn sections_are_optional() {
+        let config: Config = toml::from_str("version = 2").unwrap();
+        assert!(config.aliases.is_empty());
+        assert!(config.templates.is_empty());
+    }
+
+    #[test]
+    fn initialized_config_is_valid() {
+        let dir = TempDir::new("init");
+        let file = dir.path().join("config.toml");
+        Config::create(file.clone(), false).unwrap();
+
+        let problems = Config::problems(&file, dir.path());
+
+        assert_eq!(problems, Vec::<String>::new())
+    }
+
+    #[test]
+    fn reports_invalid_registries() {
+        let dir = TempDir::new("registries");
+        let file = dir.write(
+            "config.toml",
+            r#"
+            version = 2
+
+            [registries.both]
+            git = "https://example.com/registry.git"
+            path = "/registry"
+
+            [registries.missing]
+            path = "/does/not/exist"
+            "#,
+        );
+
+        let problems = Config::problems(&file, dir.path());
+
+        assert_eq!(
+            problems,
+            vec![
+                "Registry both must have exactly one of `git` or `path`",
+                "Registry missing has no /does/not/exist/registry.toml",
+            ]
+        )
+    }
+
+    #[test]
+    fn resolves_registries_relative_to_the_config() {
+        let dir = TempDir::new("relative-registry");
+        dir.write("shared/registry.toml", "");
+        let file = dir.write(
+            "config.toml",
+            r#"
+            version = 2
+
+            [registries.local]
+            path = "shared"
+            "#,
+        );
+
+        let problems = Config::problems(&file, dir.path());
+
+        assert_eq!(problems, Vec::<String>::new())
+    }
+
+    #[test]
+    fn sets_nested_keys() {
+        let dir = TempDir::new("set");
+        let file = dir.write("config.toml", "version = 2");
+        let mut config = Config::read(&file).unwrap();
+        config.set("detection.threshold", "0.4").unwrap();
+        config.set("variables.build_dir", "out").unwrap();
+
+        let config = Config::read(&file).unwrap();
+
+        assert!((config.detection.threshold.unwrap() - 0.4).abs() < 1e-9);
+        assert_eq!(config.variables["build_dir"], "out");
+    }
+
+    #[test]
+    fn refuses_to_set_invalid_keys() {
+        let dir = TempDir::new("set-invalid");
+        let file = dir.write("config.toml", "version = 2");
+        let mut config = Config::read(&file).unwrap();
+
+        let unknown = config.set("unknown.key", "1").unwrap_err();
+        let not_a_string = config.set("server.url", "x").unwrap_err();
+        let wrong_type = config.set("detection.threshold", "high").unwrap_err();
+        let typo = config.set("detection.treshhold", "0.4");
+        let version = config.set("version", "7");
+        let contents = read_to_string(&file).unwrap();
+
+        assert!(unknown.to_string().starts_with("Unknown key unknown.key"));
+        assert_eq!(
+            not_a_string.to_string(),
+            "Invalid value \"x\" for server.url"
+        );
+        assert_eq!(
+            wrong_type.to_string(),
+            "Invalid value \"high\" for detection.threshold"
+        );
+        assert!(typo.is_err() && version.is_err());
+        assert_eq!(contents, "version = 2");
+    }
+
+    #[test]
+    fn keeps_unknown_entries_when_setting_keys() {
+        let dir = TempDir::new("set-unknown");
+        let file = dir.write("config.toml", "version = 2\nunknown = true\n");
+        let mut config = Config::read(&file).unwrap();
+        config.set("server", "https://example.com").unwrap();
+        let contents: toml::value::Table = toml::from_str(&read_to_string(&file).unwrap()).unwrap();
+
+        assert_eq!(contents["unknown"], toml::Value::Boolean(true));
+        assert_eq!(
+            contents["server"],
+            toml::Value::String("https://example.com".to_string())
+        );
+    }
+
+    #[test]
+    fn validates_config() {
+        let dir = TempDir::new("valid");
+        let file = dir.write(
+            "config.toml",
+            r#"
+            version = 2
+            defaults = ["macos"]
+
+            [aliases]
+            web = ["node", "macos"]
+
+            [detection]
+            threshold = 0.4
+            "#,
+        );
+
+        let result = Config::validate(&file, dir.path());
+
+        assert!(result.is_ok())
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let dir = TempDir::new("invalid");
+        let file = dir.write(
+            "config.toml",
+            r#"
+            version = 2
+            unknown = true
+
+            [aliases]
+            empty = []
+
+            [templates]
+            docs = "docs.txt"
+            "#,
+        );
+
+        let result = Config::validate(&file, dir.path());
+        let problems = Config::problems(&file, dir.path());
+
+        assert!(result.is_err());
+        assert_eq!(
+            problems,
+            vec![
+                "unknown key unknown",
+                "template docs refers to missing file docs.txt",
+                "alias empty is empty",
+            ]
+        )
+    }
+
+    #[test]
+    fn reports_unknown_nested_keys() {
+        let dir = TempDir::new("nested");
+        let file = dir.write("config.toml", "version = 2\n[detection]\ntreshhold = 0.4\n");
+
+        let problems = Config::problems(&file, dir.path());
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("unknown field `treshhold`"));
+    }
+
+    #[test]
+    fn refuses_to_overwrite_templates_without_force() {
+        let dir = TempDir::new("add-template");
+        let file = dir.write("templates/docs.txt", "/docs\n");
+        let mut config = dir.config();
+
+        let refused = config.add_template("docs".to_string(), "docs.txt".to_string(), false);
+        let kept = read_to_string(&file).unwrap();
+        config
+            .add_template("docs".to_string(), "docs.txt".to_string(), true)
+            .unwrap();
+
+        assert!(refused.is_err());
+        assert_eq!(kept, "/docs\n");
+        assert_eq!(read_to_string(&file).unwrap(), "\n### docs ###\n");
+        assert_eq!(config.templates["docs"], "docs.txt");
+    }
+
+    #[test]
+    fn deletes_removed_templates() {
+        let dir = TempDir::new("delete-template");
+        let file = dir.write("templates/docs.txt", "/docs\n");
+        let mut config = dir.config();
+        config
+            .templates
+            .insert("docs".to_string(), "docs.txt".to_string());
+
+        config.remove_template("docs", true, false).unwrap();
+
+        assert!(config.templates.is_empty());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn archives_removed_templates_without_overwriting() {
+        let dir = TempDir::new("archive-template");
+        let mut config = dir.config();
+        for contents in ["/first\n", "/second\n"] {
+            dir.write("templates/docs.txt", contents);
+            config
+                .templates
+                .insert("docs".to_string(), "docs.txt".to_string());
+            config.remove_template("docs", false, true).unwrap();
+        }
+        let archive = dir.path().join("templates/.archive");
+
+        assert!(!dir.path().join("templates/docs.txt").exists());
+        assert_eq!(
+            read_to_string(archive.join("docs.txt")).unwrap(),
+            "/first\n"
+        );
+        assert_eq!(
+            read_to_string(archive.join("docs-1.txt")).unwrap(),
+            "/second\n"
+        );
+    }
+
+    #[test]
+    fn checks_templates_against_files() {
+        let dir = TempDir::new("check-templates");
+        dir.write("templates/docs.txt", "/docs\n");
+        dir.write("templates/orphan.txt", "/orphan\n");
+        dir.write("templates/.archive/old.txt", "/old\n");
+        let mut config = dir.config();
+        for (name, file_name) in [("docs", "docs.txt"), ("gone", "gone.txt")] {
+            config
+                .templates
+                .insert(name.to_string(), file_name.to_string());
+        }
+
+        let (orphaned, missing) = config.template_mismatches().unwrap();
+
+        assert_eq!(orphaned, vec!["orphan.txt"]);
+        assert_eq!(missing, vec!["gone"]);
+    }
+
+    #[test]
+    fn reports_parse_errors_with_their_location_once() {
+        let dir = TempDir::new("parse-error");
+        let file = dir.write("config.toml", "version = 2\n[aliases\n");
+
+        let error = Config::read(&file).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Could not parse config file {}:2:9\nexpected a right bracket, found a newline",
+                file.display()
+            )
+        );
+    }
+
+    #[test]
+    fn refuses_to_init_over_a_broken_config() {
+        let dir = TempDir::new("init-broken");
+        let file = dir.write("config.toml", "version = 2\n[aliases\n");
+
+        let refused = Config::create(file.clone(), false);
+
+        assert!(refused.is_err());
+        assert_eq!(read_to_string(&file).unwrap(), "version = 2\n[aliases\n");
+    }
+
+    #[test]
+    fn finds_project_file_up_to_the_repository_root() {
+        let dir = TempDir::new("project-file");
+        let outside = dir.write(PROJECT_FILE, "");
+        let inside = dir.write(&format!("repo/{}", PROJECT_FILE), "");
+        dir.write("repo/.git/HEAD", "");
+        dir.write("other/.git/HEAD", "");
+        dir.write("other/src/main.rs", "");
+
+        assert_eq!(project_file(&dir.path().join("repo/src")), Some(inside));
+        assert_eq!(project_file(&dir.path().join("other/src")), None);
+        assert_eq!(project_file(dir.path()), Some(outside));
+    }
+
+    #[test]
+    fn reads_paths_relative_to_the_file() {
+        let dir = TempDir::new("read-relative");
+        let file = dir.write(
+            &format!("project/{}", PROJECT_FILE),
+            r#"
+            version = 2
+
+            [templates]
+            docs = "docs.txt"
+
+            [registries.local]
+            path = "shared"
+            "#,
+        );
+
+        let config = Config::read_relative(&file).unwrap();
+        let project = dir.path().join("project");
+
+        assert_eq!(
+            config.templates["docs"],
+            project.join("docs.txt").to_string_lossy()
+        );
+        assert_eq!(
+            config.registries["local"].path,
+            Some(project.join("shared"))
+        );
+    }
+
+    #[test]
+    fn merges_with_precedence_to_the_other_config() {
+        let mut config: Config = toml::from_str(
+            r#"
+            defaults = ["rust", "macos"]
+            [aliases]
+            web = ["node"]
+            mine = ["rust"]
+            [detection]
+            max_depth = 2
+            threshold = 0.5
+            "#,
+        )
+        .unwrap();
+        let other: Config = toml::from_str(
+            r#"
+            defaults = ["macos", "node"]
+            [aliases]
+            web = ["react"]
+            [detection]
+            max_depth = 4
+            "#,
+        )
+        .unwrap();
+
+        config.merge(&other);
+
+        assert_eq!(config.defaults, vec!["rust", "macos", "node"]);
+        assert_eq!(config.aliases["web"], vec!["react"]);
+        assert_eq!(config.aliases["mine"], vec!["rust"]);
+        assert_eq!(config.detection.max_depth, Some(4));
+        assert_eq!(config.detection.threshold, Some(0.5));
+    }
+}
@@ -1,24 +1,303 @@
-use std::ffi::OsString;
+use crate::manifest;
+use anyhow::{bail, Context, Result};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+/// The confidence a template needs to be detected when none is configured.
+pub const DEFAULT_THRESHOLD: f64 = 0.5;
+/// The weight of matchers that are good evidence of a template on their own.
+const STRONG: f64 = 1.0;
+/// The weight of matchers for files that are often found outside of projects
+/// using the template, like `__init__.py`.
+const WEAK: f64 = 0.3;
+
+/// Settings for `--auto`, from the `[detection]` section of the config.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DetectionConfig {
+    /// How many levels of directories to scan, the current directory is 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// Whether to detect templates from the environment, like `--env`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<bool>,
+    /// Whether to only look at files tracked by git, like `--tracked`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracked: Option<bool>,
+    /// The confidence from 0 to 1 a template needs to be detected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// Names of directories to skip in addition to the built-in ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip: Vec<String>,
+    /// Names of detectors to turn off, built-in or not
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable: Vec<String>,
+    /// Detectors by the template or alias they add, replacing a built-in
+    /// detector with the same name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub detectors: BTreeMap<String, DetectorConfig>,
+}
+
+impl DetectionConfig {
+    pub fn merge(&mut self, other: &DetectionConfig) {
+        if other.max_depth.is_some() {
+            self.max_depth = other.max_depth;
+        }
+        if other.environment.is_some() {
+            self.environment = other.environment;
+        }
+        if other.tracked.is_some() {
+            self.tracked = other.tracked;
+        }
+        if other.threshold.is_some() {
+            self.threshold = other.threshold;
+        }
+        for skip in &other.skip {
+            if !self.skip.contains(skip) {
+                self.skip.push(skip.clone());
+            }
+        }
+        for disable in &other.disable {
+            if !self.disable.contains(disable) {
+                self.disable.push(disable.clone());
+            }
+        }
+        self.detectors.extend(other.detectors.clone());
+    }
+
+    /// Describes the detectors that have invalid patterns or no matchers.
+    pub fn problems(&self) -> Vec<String> {
+        self.detectors
+            .iter()
+            .filter_map(|(name, config)| match Detector::from_config(name, config) {
+                Ok(detector) if detector.matchers.is_empty() => {
+                    Some(format!("detector {} has no matchers", name))
+                }
+                Ok(_) => None,
+                Err(e) => Some(format!("{:#}", e)),
+            })
+            .collect()
+    }
+}
+
+/// A detector from the config, every entry of every list is a matcher.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DetectorConfig {
+    /// The weight from 0 to 1 of the matchers, 1 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub globs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regexes: Vec<String>,
+    /// Globs for entries that never count for this detector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Detectors that don't count the entries this detector matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppresses: Vec<String>,
+    /// Dependencies by the manifest declaring them, like `Cargo.toml`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
 
 #[derive(Debug)]
 pub struct Detectors {
     detectors: Vec<Detector>,
+    /// Detectors turned off in the config, kept to explain the detection
+    disabled: Vec<Detector>,
+    threshold: f64,
+}
+
+/// How a single matcher of a detector related to an entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Finding {
+    Matched,
+    /// The matcher matched, but its detector is disabled in the config
+    Disabled,
+    /// The entry almost matched, for the given reason
+    NearMiss(String),
+    /// The matcher matched, but the entry is claimed by the given detector
+    /// that suppresses this one
+    Suppressed(String),
+}
+
+/// Why a detector fired, or almost fired, see `Detectors::explain`.
+#[derive(Debug)]
+pub struct Explanation {
+    pub template: String,
+    pub matcher: String,
+    pub weight: f64,
+    pub path: PathBuf,
+    pub finding: Finding,
 }
 
 impl Detectors {
+    /// The built-in detectors combined with the ones from the config.
+    pub fn from_config(config: &DetectionConfig) -> Result<Self> {
+        let mut detectors = Detectors::default().detectors;
+        detectors.retain(|detector| !config.detectors.contains_key(&detector.template));
+        for (name, detector) in &config.detectors {
+            detectors.push(Detector::from_config(name, detector)?);
+        }
+        let (disabled, detectors) = detectors
+            .into_iter()
+            .partition(|detector| config.disable.contains(&detector.template));
+
+        let threshold = config.threshold.unwrap_or(DEFAULT_THRESHOLD);
+        if !(0.0..=1.0).contains(&threshold) {
+            bail!("The detection threshold must be between 0 and 1");
+        }
+
+        Ok(Detectors {
+            detectors,
+            disabled,
+            threshold,
+        })
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// The templates or aliases the enabled detectors add.
+    pub fn templates(&self) -> Vec<&str> {
+        self.detectors
+            .iter()
+            .map(|detector| detector.template.as_str())
+            .collect()
+    }
+
+    /// The templates or aliases the disabled detectors would add.
+    pub fn disabled_templates(&self) -> Vec<&str> {
+        self.disabled
+            .iter()
+            .map(|detector| detector.template.as_str())
+            .collect()
+    }
+
+    /// Lists the matchers and entries that made each detector fire. For the
+    /// detectors that did not fire it lists the entries they almost matched,
+    /// and for disabled detectors the entries they would have matched.
+    pub fn explain<E: DirEntry>(&self, entries: &[E]) -> Vec<Explanation> {
+        let matched = self.matched(entries);
+        let scores: BTreeMap<String, f64> = self.scores(entries).into_iter().collect();
+        let below_threshold = |mut explanation: Explanation| {
+            let score = scores.get(&explanation.template).copied().unwrap_or(0.0);
+            if explanation.finding == Finding::Matched && score < self.threshold {
+                explanation.finding = Finding::NearMiss(format!(
+                    "only gives {:.0}% confidence, below the {:.0}% threshold",
+                    score * 100.0,
+                    self.threshold * 100.0
+                ));
+            }
+            explanation
+        };
+        let enabled = self.detectors.iter().flat_map(|detector| {
+            detector
+                .explain(entries, false, |index| {
+                    self.suppressor(detector, &matched, index)
+                        .map(|suppressor| suppressor.template.clone())
+                })
+                .into_iter()
+                .map(below_threshold)
+        });
+        let disabled = self
+            .disabled
+            .iter()
+            .flat_map(|detector| detector.explain(entries, true, |_| None));
+
+        enabled.chain(disabled).collect()
+    }
+
+    /// The templates with a confidence of at least the threshold.
     pub fn detects<E: DirEntry>(&self, entries: &[E]) -> Vec<String> {
+        self.scores(entries)
+            .into_iter()
+            .filter(|(_, score)| *score >= self.threshold)
+            .map(|(template, _)| template)
+            .collect()
+    }
+
+    /// The confidence in every template a detector matched, combining the
+    /// weights of the matchers that matched entries not claimed by a detector
+    /// suppressing it. Two weak matches give more confidence than one.
+    pub fn scores<E: DirEntry>(&self, entries: &[E]) -> Vec<(String, f64)> {
+        let matched = self.matched(entries);
         self.detectors
             .iter()
-            .filter_map(|detector| detector.detects(entries))
+            .zip(&matched)
+            .filter_map(|(detector, indices)| {
+                let doubt: f64 = detector
+                    .matchers
+                    .iter()
+                    .zip(indices)
+                    .filter(|(_, indices)| {
+                        indices
+                            .iter()
+                            .any(|index| self.suppressor(detector, &matched, *index).is_none())
+                    })
+                    .map(|((_, weight), _)| 1.0 - weight)
+                    .product();
+
+                let score = 1.0 - doubt;
+                (score > 0.0).then(|| (detector.template.clone(), score))
+            })
+            .collect()
+    }
+
+    /// The indices of the entries every matcher of every detector matched.
+    fn matched<E: DirEntry>(&self, entries: &[E]) -> Vec<Vec<BTreeSet<usize>>> {
+        self.detectors
+            .iter()
+            .map(|detector| detector.matched(entries))
             .collect()
     }
+
+    /// The detector that suppresses `detector` and matched the entry at
+    /// `index` as well, if any.
+    fn suppressor(
+        &self,
+        detector: &Detector,
+        matched: &[Vec<BTreeSet<usize>>],
+        index: usize,
+    ) -> Option<&Detector> {
+        self.detectors
+            .iter()
+            .zip(matched)
+            .find(|(other, indices)| {
+                other.suppresses.contains(&detector.template)
+                    && indices.iter().any(|indices| indices.contains(&index))
+            })
+            .map(|(other, _)| other)
+    }
 }
 
 impl Default for Detectors {
     /// Based on https://github.com/starship/starship/tree/master/src/configs
     fn default() -> Self {
         let detectors = vec![
+            Detector::new(
+                "angular",
+                [Matcher::by_dependency("package.json", "@angular/core")],
+            ),
             Detector::new("crystal", [Matcher::by_file_name("shard.yml")]),
+            Detector::new("csharp", [Matcher::by_glob("*.csproj")]),
             Detector::new(
                 "dart",
                 [
@@ -27,6 +306,13 @@ impl Default for Detectors {
                     Matcher::by_file_name("pubspec.lock"),
                 ],
             ),
+            Detector::new(
+                "django",
+                [
+                    Matcher::by_dependency("pyproject.toml", "django"),
+                    Matcher::by_dependency("requirements.txt", "django"),
+                ],
+            ),
             Detector::new("elixir", [Matcher::by_file_name("mix.exs")]),
             Detector::new(
                 "elm",
@@ -43,6 +329,21 @@ impl Default for Detectors {
                     Matcher::by_file_name("erlang.mk"),
                 ],
             ),
+            Detector::new(
+                "flask",
+                [
+                    Matcher::by_dependency("pyproject.toml", "flask"),
+                    Matcher::by_dependency("requirements.txt", "flask"),
+                ],
+            ),
+            Detector::new(
+                "flutter",
+                [
+                    Matcher::by_dependency("pubspec.yaml", "flutter"),
+                    Matcher::by_dependency("pubspec.yml", "flutter"),
+                ],
+            ),
+            Detector::new("fsharp", [Matcher::by_glob("*.fsproj")]),
             Detector::new(
                 "haskell",
                 [
@@ -75,6 +376,16 @@ impl Default for Detectors {
                     Matcher::by_file_name("build.boot"),
                 ],
             ),
+            Detector::new(
+                "gradle",
+                [
+                    Matcher::by_dir_name(".gradle"),
+                    Matcher::by_file_name("gradlew"),
+                    Matcher::by_file_name("settings.gradle"),
+                    Matcher::by_file_name("settings.gradle.kts"),
+                ],
+            ),
+            Detector::new("jetbrains", [Matcher::by_dir_name(".idea")]),
             Detector::new(
                 "julia",
                 [
@@ -82,6 +393,11 @@ impl Default for Detectors {
                     Matcher::by_file_name("Manifest.toml"),
                 ],
             ),
+            Detector::new(
+                "laravel",
+                [Matcher::by_dependency("composer.json", "laravel/framework")],
+            ),
+            Detector::new("nextjs", [Matcher::by_dependency("package.json", "next")]),
             Detector::new("nim", [Matcher::by_file_name("nim.cfg")]),
             Detector::new(
                 "node",
@@ -91,6 +407,7 @@ impl Default for Detectors {
                     Matcher::by_file_name(".nvmrc"),
                 ],
             ),
+            Detector::new("nuxtjs", [Matcher::by_dependency("package.json", "nuxt")]),
             Detector::new(
                 "ocaml",
                 [
@@ -137,10 +454,21 @@ impl Default for Detectors {
                     Matcher::by_file_name("Pipfile"),
                     Matcher::by_file_name("tox.ini"),
                     Matcher::by_file_name("setup.py"),
-                    Matcher::by_file_name("__init__.py"),
+                    // Python extension modules written in Rust
+                    Matcher::by_dependency("Cargo.toml", "pyo3"),
                 ],
-            ),
+            )
+            .weak([
+                Matcher::by_file_name("__init__.py"),
+                Matcher::by_dir_name(".venv"),
+            ])
+            .excluding([Matcher::by_glob("**/node_modules/**")]),
             Detector::new("r", [Matcher::by_file_name(".Rprofile")]),
+            Detector::new("rails", [Matcher::by_dependency("Gemfile", "rails")]),
+            Detector::new(
+                "reactnative",
+                [Matcher::by_dependency("package.json", "react-native")],
+            ),
             Detector::new(
                 "ruby",
                 [
@@ -157,67 +485,256 @@ impl Default for Detectors {
                     Matcher::by_file_name(".sbtenv"),
                     Matcher::by_file_name("build.sbt"),
                 ],
-            ),
+            )
+            .suppressing(["java"]),
             Detector::new("swift", [Matcher::by_file_name("Package.swift")]),
+            Detector::new(
+                "symfony",
+                [Matcher::by_dependency(
+                    "composer.json",
+                    "symfony/framework-bundle",
+                )],
+            ),
+            Detector::new(
+                "terraform",
+                [
+                    Matcher::by_dir_name(".terraform"),
+                    Matcher::by_file_name(".terraform.lock.hcl"),
+                    Matcher::by_glob("*.tf"),
+                    Matcher::by_glob("*.tfvars"),
+                ],
+            ),
+            Detector::new(
+                "unity",
+                [
+                    Matcher::by_glob("**/ProjectSettings/ProjectVersion.txt"),
+                    Matcher::by_glob("*.unity"),
+                ],
+            ),
+            Detector::new("unrealengine", [Matcher::by_glob("*.uproject")]),
+            Detector::new(
+                "visualstudio",
+                [Matcher::by_regex(r"\.(sln|vcxproj|vcproj)$")],
+            ),
+            Detector::new("visualstudiocode", [Matcher::by_dir_name(".vscode")]),
             Detector::new("zig", [Matcher::by_file_extension("zig")]),
         ];
-        Detectors { detectors }
+        Detectors {
+            detectors,
+            disabled: Vec::new(),
+            threshold: DEFAULT_THRESHOLD,
+        }
     }
 }
 
 #[derive(Debug)]
 struct Detector {
     template: String,
-    matchers: Vec<Matcher>,
+    /// Matchers and their weight, from 0 to 1
+    matchers: Vec<(Matcher, f64)>,
+    /// Entries matching any of these never count for this detector
+    exclusions: Vec<Matcher>,
+    /// Detectors that don't count the entries this detector matched
+    suppresses: Vec<String>,
 }
 
 impl Detector {
     fn new<T: Into<String>, MS: Into<Vec<Matcher>>>(template: T, matchers: MS) -> Self {
         Detector {
             template: template.into(),
-            matchers: matchers.into(),
+            matchers: matchers
+                .into()
+                .into_iter()
+                .map(|matcher| (matcher, STRONG))
+                .collect(),
+            exclusions: Vec::new(),
+            suppresses: Vec::new(),
+        }
+    }
+
+    /// Adds matchers that are only weak evidence for the template.
+    fn weak<MS: Into<Vec<Matcher>>>(mut self, matchers: MS) -> Self {
+        let weak = matchers.into().into_iter().map(|matcher| (matcher, WEAK));
+        self.matchers.extend(weak);
+        self
+    }
+
+    fn excluding<MS: Into<Vec<Matcher>>>(mut self, exclusions: MS) -> Self {
+        self.exclusions = exclusions.into();
+        self
+    }
+
+    fn suppressing<T: Into<String>, TS: IntoIterator<Item = T>>(mut self, templates: TS) -> Self {
+        self.suppresses = templates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn from_config(template: &str, config: &DetectorConfig) -> Result<Self> {
+        let mut matchers: Vec<Matcher> = config
+            .files
+            .iter()
+            .map(Matcher::by_file_name)
+            .chain(
+                config
+                    .extensions
+                    .iter()
+                    .map(|extension| Matcher::by_file_extension(extension.trim_start_matches('.'))),
+            )
+            .chain(config.dirs.iter().map(Matcher::by_dir_name))
+            .collect();
+
+        for glob in &config.globs {
+            let pattern = Pattern::new(glob)
+                .with_context(|| format!("detector {} has invalid glob {}", template, glob))?;
+            matchers.push(Matcher::ByGlob(pattern));
         }
+        for regex in &config.regexes {
+            let regex = Regex::new(regex)
+                .with_context(|| format!("detector {} has invalid regex {}", template, regex))?;
+            matchers.push(Matcher::ByRegex(regex));
+        }
+        for (manifest, dependencies) in &config.dependencies {
+            if !manifest::SUPPORTED.contains(&manifest.as_str()) {
+                bail!(
+                    "detector {} reads dependencies from {}, which is not one of {}",
+                    template,
+                    manifest,
+                    manifest::SUPPORTED.join(", ")
+                );
+            }
+            matchers.extend(
+                dependencies
+                    .iter()
+                    .map(|dependency| Matcher::by_dependency(manifest, dependency)),
+            );
+        }
+
+        let weight = config.weight.unwrap_or(STRONG);
+        if !(0.0..=1.0).contains(&weight) {
+            bail!("detector {} has a weight outside of 0 to 1", template);
+        }
+
+        let mut exclusions = Vec::new();
+        for glob in &config.exclude {
+            let pattern = Pattern::new(glob)
+                .with_context(|| format!("detector {} has invalid exclusion {}", template, glob))?;
+            exclusions.push(Matcher::ByGlob(pattern));
+        }
+
+        let mut detector = Detector::new(template, [])
+            .excluding(exclusions)
+            .suppressing(&config.suppresses);
+        detector.matchers = matchers
+            .into_iter()
+            .map(|matcher| (matcher, weight))
+            .collect();
+
+        Ok(detector)
+    }
+
+    /// The indices of the entries each matcher matched, except the excluded.
+    fn matched<E: DirEntry>(&self, entries: &[E]) -> Vec<BTreeSet<usize>> {
+        self.matchers
+            .iter()
+            .map(|(matcher, _)| {
+                entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entry)| {
+                        matcher.matches(*entry) && self.excluded_by(*entry).is_none()
+                    })
+                    .map(|(index, _)| index)
+                    .collect()
+            })
+            .collect()
     }
 
-    fn detects<E: DirEntry>(&self, entries: &[E]) -> Option<String> {
-        let result = self
-            .matchers
+    fn excluded_by<E: DirEntry>(&self, entry: &E) -> Option<&Matcher> {
+        self.exclusions
             .iter()
-            .any(|matcher| entries.iter().any(|entry| matcher.matches(entry)));
-        if result {
-            Some(self.template.clone())
+            .find(|exclusion| exclusion.matches(entry))
+    }
+
+    /// The first entry each matcher matched, or if none did, every near miss.
+    /// `suppressed_by` gives the detector suppressing this one for an entry.
+    fn explain<E: DirEntry, F: Fn(usize) -> Option<String>>(
+        &self,
+        entries: &[E],
+        disabled: bool,
+        suppressed_by: F,
+    ) -> Vec<Explanation> {
+        let mut matched = Vec::new();
+        let mut near_misses = Vec::new();
+        let explanation = |matcher: &Matcher, weight, entry: &E, finding| Explanation {
+            template: self.template.clone(),
+            matcher: matcher.to_string(),
+            weight,
+            path: entry.relative_path(),
+            finding,
+        };
+
+        for (matcher, weight) in &self.matchers {
+            let explanation = |entry: &E, finding| explanation(matcher, *weight, entry, finding);
+            for (index, entry) in entries.iter().enumerate() {
+                match matcher.check(entry) {
+                    Check::Match => {
+                        if let Some(exclusion) = self.excluded_by(entry) {
+                            let reason = format!("is excluded by {}", exclusion);
+                            near_misses.push(explanation(entry, Finding::NearMiss(reason)));
+                        } else if disabled {
+                            matched.push(explanation(entry, Finding::Disabled));
+                            break;
+                        } else if let Some(suppressor) = suppressed_by(index) {
+                            let finding = Finding::Suppressed(suppressor);
+                            near_misses.push(explanation(entry, finding));
+                        } else {
+                            matched.push(explanation(entry, Finding::Matched));
+                            break;
+                        }
+                    }
+                    Check::NearMiss(reason) => {
+                        near_misses.push(explanation(entry, Finding::NearMiss(reason)))
+                    }
+                    Check::NoMatch => {}
+                }
+            }
+        }
+
+        if !matched.is_empty() || disabled {
+            matched
         } else {
-            None
+            near_misses
         }
     }
 }
 
 pub trait DirEntry {
+    /// The path of the entry relative to the directory being scanned.
+    fn relative_path(&self) -> PathBuf;
     fn file_name(&self) -> OsString;
     fn extension(&self) -> Option<OsString>;
     fn is_file(&self) -> bool;
-}
-
-impl DirEntry for std::fs::DirEntry {
-    fn file_name(&self) -> OsString {
-        self.file_name()
-    }
-
-    fn extension(&self) -> Option<OsString> {
-        let path = self.path();
-        path.extension().map(OsString::from)
-    }
-
-    fn is_file(&self) -> bool {
-        let path = self.path();
-        path.is_file()
-    }
+    fn is_dir(&self) -> bool;
+    /// The contents of the file, `None` if it is not a readable text file.
+    fn read_to_string(&self) -> Option<String>;
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum Matcher {
     ByFileExtension(OsString),
     ByFileName(OsString),
+    ByDirName(OsString),
+    /// Matched against the file name, or the relative path if the pattern
+    /// contains a `/`
+    ByGlob(Pattern),
+    /// Matched against both the file name and the relative path
+    ByRegex(Regex),
+    /// A dependency declared in a package manifest, see `manifest::dependencies`
+    ByDependency {
+        manifest: OsString,
+        dependency: String,
+    },
 }
 
 impl Matcher {
@@ -229,38 +746,179 @@ impl Matcher {
         Self::ByFileName(name.into())
     }
 
+    fn by_dir_name<T: Into<OsString>>(name: T) -> Self {
+        Self::ByDirName(name.into())
+    }
+
+    fn by_glob(pattern: &str) -> Self {
+        Self::ByGlob(Pattern::new(pattern).expect("Invalid glob in built-in detector"))
+    }
+
+    fn by_regex(pattern: &str) -> Self {
+        Self::ByRegex(Regex::new(pattern).expect("Invalid regex in built-in detector"))
+    }
+
+    fn by_dependency<T: Into<OsString>, D: Into<String>>(manifest: T, dependency: D) -> Self {
+        Self::ByDependency {
+            manifest: manifest.into(),
+            dependency: dependency.into(),
+        }
+    }
+
     fn matches<E: DirEntry>(&self, entry: &E) -> bool {
+        self.check(entry) == Check::Match
+    }
+
+    fn check<E: DirEntry>(&self, entry: &E) -> Check {
+        let matched = |matched| {
+            if matched {
+                Check::Match
+            } else {
+                Check::NoMatch
+            }
+        };
+
+        match self {
+            Self::ByFileName(name) if &entry.file_name() == name => match entry.is_file() {
+                true => Check::Match,
+                false => Check::NearMiss("is not a file".into()),
+            },
+            Self::ByFileName(_) => Check::NoMatch,
+            Self::ByFileExtension(extension) if entry.extension().as_ref() == Some(extension) => {
+                match entry.is_file() {
+                    true => Check::Match,
+                    false => Check::NearMiss("is not a file".into()),
+                }
+            }
+            Self::ByFileExtension(_) => Check::NoMatch,
+            Self::ByDirName(name) if &entry.file_name() == name => match entry.is_dir() {
+                true => Check::Match,
+                false => Check::NearMiss("is not a directory".into()),
+            },
+            Self::ByDirName(_) => Check::NoMatch,
+            Self::ByGlob(pattern) => {
+                let options = MatchOptions {
+                    require_literal_separator: true,
+                    ..MatchOptions::default()
+                };
+
+                matched(if pattern.as_str().contains('/') {
+                    pattern.matches_with(&slash_path(&entry.relative_path()), options)
+                } else {
+                    pattern.matches_with(&entry.file_name().to_string_lossy(), options)
+                })
+            }
+            Self::ByRegex(regex) => matched(
+                regex.is_match(&entry.file_name().to_string_lossy())
+                    || regex.is_match(&slash_path(&entry.relative_path())),
+            ),
+            Self::ByDependency {
+                manifest,
+                dependency,
+            } if entry.is_file() && &entry.file_name() == manifest => {
+                let dependencies = entry.read_to_string().and_then(|contents| {
+                    manifest::dependencies(&manifest.to_string_lossy(), &contents)
+                });
+
+                match dependencies {
+                    Some(dependencies) if dependencies.contains(dependency) => Check::Match,
+                    Some(_) => Check::NearMiss(format!("does not depend on {}", dependency)),
+                    None => Check::NearMiss("could not be read".into()),
+                }
+            }
+            Self::ByDependency { .. } => Check::NoMatch,
+        }
+    }
+}
+
+impl Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::ByFileName(name) => entry.is_file() && &entry.file_name() == name,
             Self::ByFileExtension(extension) => {
-                entry.is_file() && entry.extension() == Some(extension.clone())
+                write!(f, "extension {}", extension.to_string_lossy())
             }
+            Self::ByFileName(name) => write!(f, "file {}", name.to_string_lossy()),
+            Self::ByDirName(name) => write!(f, "directory {}", name.to_string_lossy()),
+            Self::ByGlob(pattern) => write!(f, "glob {}", pattern),
+            Self::ByRegex(regex) => write!(f, "regex {}", regex),
+            Self::ByDependency {
+                manifest,
+                dependency,
+            } => write!(
+                f,
+                "dependency {} in {}",
+                dependency,
+                manifest.to_string_lossy()
+            ),
         }
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum Check {
+    Match,
+    NearMiss(String),
+    NoMatch,
+}
+
+/// Joins the components of a path with `/`, regardless of platform.
+fn slash_path(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::detector::{Detectors, DirEntry};
-    use std::ffi::OsString;
+    use crate::detector::{DetectionConfig, DetectorConfig, Detectors, DirEntry, Finding};
+    use std::{ffi::OsString, path::PathBuf};
 
     struct FakeDirEntry {
+        path: PathBuf,
         file_name: OsString,
         extension: Option<OsString>,
         is_file: bool,
+        contents: Option<String>,
     }
 
     impl FakeDirEntry {
         fn new<T: Into<OsString>>(file_name: T, extension: Option<T>, is_file: bool) -> Self {
+            let file_name = file_name.into();
             FakeDirEntry {
-                file_name: file_name.into(),
+                path: PathBuf::from(&file_name),
+                file_name,
                 extension: extension.map(|pe| pe.into()),
                 is_file,
+                contents: None,
+            }
+        }
+
+        fn with_path(path: &str, is_file: bool) -> Self {
+            let path = PathBuf::from(path);
+            FakeDirEntry {
+                file_name: path.file_name().unwrap().into(),
+                extension: path.extension().map(OsString::from),
+                path,
+                is_file,
+                contents: None,
+            }
+        }
+
+        fn with_contents(path: &str, contents: &str) -> Self {
+            FakeDirEntry {
+                contents: Some(contents.to_string()),
+                ..FakeDirEntry::with_path(path, true)
             }
         }
     }
 
     impl DirEntry for FakeDirEntry {
+        fn relative_path(&self) -> PathBuf {
+            self.path.clone()
+        }
+
         fn file_name(&self) -> OsString {
             self.file_name.clone()
         }
@@ -272,6 +930,14 @@ mod tests {
         fn is_file(&self) -> bool {
             self.is_file
         }
+
+        fn is_dir(&self) -> bool {
+            !self.is_file
+        }
+
+        fn read_to_string(&self) -> Option<String> {
+            self.contents.clone()
+        }
     }
 
     #[test]
@@ -343,4 +1009,235 @@ mod tests {
         let result = Detectors::default().detects(&Vec::from([entry]));
         assert_eq!(result, vec!["rust"])
     }
+
+    #[test]
+    fn detects_jetbrains_from_idea_dir() {
+        let entry = FakeDirEntry::new(".idea", None, false);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["jetbrains"])
+    }
+
+    #[test]
+    fn detects_visualstudiocode_from_vscode_dir() {
+        let entry = FakeDirEntry::new(".vscode", None, false);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["visualstudiocode"])
+    }
+
+    #[test]
+    fn detects_terraform_from_terraform_dir() {
+        let entry = FakeDirEntry::new(".terraform", None, false);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["terraform"])
+    }
+
+    #[test]
+    fn does_not_detect_dir_matchers_from_files() {
+        let entry = FakeDirEntry::new(".idea", None, true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert!(result.is_empty())
+    }
+
+    #[test]
+    fn detects_csharp_from_csproj_glob() {
+        let entry = FakeDirEntry::with_path("src/App/App.csproj", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["csharp"])
+    }
+
+    #[test]
+    fn detects_terraform_from_tf_glob() {
+        let entry = FakeDirEntry::with_path("infra/main.tf", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["terraform"])
+    }
+
+    #[test]
+    fn detects_unity_from_relative_path() {
+        let entry = FakeDirEntry::with_path("game/ProjectSettings/ProjectVersion.txt", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["unity"])
+    }
+
+    #[test]
+    fn detects_visualstudio_from_sln_regex() {
+        let entry = FakeDirEntry::with_path("App.sln", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["visualstudio"])
+    }
+
+    #[test]
+    fn detects_nextjs_from_package_json_dependencies() {
+        let entry = FakeDirEntry::with_contents(
+            "package.json",
+            r#"{"dependencies": {"next": "^13.0.0", "react": "^18.0.0"}}"#,
+        );
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["nextjs", "node"])
+    }
+
+    #[test]
+    fn detects_rails_from_gemfile() {
+        let entry = FakeDirEntry::with_contents(
+            "Gemfile",
+            "source 'https://rubygems.org'\ngem 'rails', '~> 7.0'\n",
+        );
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["rails", "ruby"])
+    }
+
+    #[test]
+    fn does_not_detect_frameworks_from_unrelated_dependencies() {
+        let entry = FakeDirEntry::with_contents("requirements.txt", "requests==2.31\n# django\n");
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["python"])
+    }
+
+    #[test]
+    fn detects_with_detectors_from_config() {
+        let config = DetectionConfig {
+            disable: vec!["java".into()],
+            detectors: [(
+                "ourbuild".to_string(),
+                DetectorConfig {
+                    files: vec!["BUILD.our".into()],
+                    globs: vec!["*.ourproj".into()],
+                    ..DetectorConfig::default()
+                },
+            )]
+            .into(),
+            ..DetectionConfig::default()
+        };
+        let entries = vec![
+            FakeDirEntry::with_path("pom.xml", true),
+            FakeDirEntry::with_path("app/app.ourproj", true),
+        ];
+        let result = Detectors::from_config(&config).unwrap().detects(&entries);
+        assert_eq!(result, vec!["ourbuild"])
+    }
+
+    #[test]
+    fn detects_python_extensions_from_cargo_toml() {
+        let entry = FakeDirEntry::with_contents(
+            "Cargo.toml",
+            "[package]\nname = \"ext\"\n\n[dependencies]\npyo3 = \"0.20\"\n",
+        );
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["python", "rust"])
+    }
+
+    #[test]
+    fn detects_dependencies_with_detectors_from_config() {
+        let detector = DetectorConfig {
+            dependencies: [("Cargo.toml".to_string(), vec!["tauri".to_string()])].into(),
+            ..DetectorConfig::default()
+        };
+        let config = DetectionConfig {
+            detectors: [("tauri".to_string(), detector.clone())].into(),
+            ..DetectionConfig::default()
+        };
+        let entries = vec![FakeDirEntry::with_contents(
+            "src-tauri/Cargo.toml",
+            "[dependencies]\ntauri = { version = \"1\" }\n",
+        )];
+        let result = Detectors::from_config(&config).unwrap().detects(&entries);
+        assert_eq!(result, vec!["rust", "tauri"]);
+
+        let unsupported = DetectionConfig {
+            detectors: [(
+                "tauri".to_string(),
+                DetectorConfig {
+                    dependencies: [("go.mod".to_string(), vec!["tauri".to_string()])].into(),
+                    ..detector
+                },
+            )]
+            .into(),
+            ..DetectionConfig::default()
+        };
+        assert!(Detectors::from_config(&unsupported).is_err())
+    }
+
+    #[test]
+    fn explains_matches_and_near_misses() {
+        let entries = vec![
+            FakeDirEntry::with_contents("Gemfile", "gem 'sinatra'\n"),
+            FakeDirEntry::with_path(".vscode", true),
+        ];
+        let explanations = Detectors::default().explain(&entries);
+        let result: Vec<_> = explanations
+            .iter()
+            .map(|e| (e.template.as_str(), e.matcher.as_str(), &e.finding))
+            .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "rails",
+                    "dependency rails in Gemfile",
+                    &Finding::NearMiss("does not depend on rails".into())
+                ),
+                ("ruby", "file Gemfile", &Finding::Matched),
+                (
+                    "visualstudiocode",
+                    "directory .vscode",
+                    &Finding::NearMiss("is not a directory".into())
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn scala_suppresses_java_for_build_sbt() {
+        let entry = FakeDirEntry::with_path("build.sbt", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert_eq!(result, vec!["scala"])
+    }
+
+    #[test]
+    fn suppression_only_applies_to_shared_entries() {
+        let entries = vec![
+            FakeDirEntry::with_path("build.sbt", true),
+            FakeDirEntry::with_path("legacy/pom.xml", true),
+        ];
+        let result = Detectors::default().detects(&entries);
+        assert_eq!(result, vec!["java", "scala"])
+    }
+
+    #[test]
+    fn does_not_detect_excluded_entries() {
+        let entry = FakeDirEntry::with_path("web/node_modules/dep/setup.py", true);
+        let result = Detectors::default().detects(&Vec::from([entry]));
+        assert!(result.is_empty())
+    }
+
+    #[test]
+    fn weak_matches_are_below_the_threshold() {
+        let entries = vec![FakeDirEntry::with_path("scripts/__init__.py", true)];
+        let detectors = Detectors::default();
+        assert!(detectors.detects(&entries).is_empty());
+
+        let scores = detectors.scores(&entries);
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].0, "python");
+        assert!((scores[0].1 - 0.3).abs() < 0.001)
+    }
+
+    #[test]
+    fn detects_julia_from_project_toml_alone() {
+        let entries = vec![FakeDirEntry::with_path("Project.toml", true)];
+        let result = Detectors::default().detects(&entries);
+        assert_eq!(result, vec!["julia"])
+    }
+
+    #[test]
+    fn combines_the_weights_of_matchers() {
+        let entries = vec![
+            FakeDirEntry::with_path("pkg/__init__.py", true),
+            FakeDirEntry::with_path(".venv", false),
+        ];
+        let scores = Detectors::default().scores(&entries);
+        assert_eq!(scores.len(), 1);
+        assert!((scores[0].1 - 0.51).abs() < 0.001)
+    }
 }
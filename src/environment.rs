@@ -0,0 +1,154 @@
+use crate::detector::DirEntry;
+use std::{env, ffi::OsString, path::Path};
+
+/// Operating systems, as in `std::env::consts::OS`, with a template of the
+/// same name.
+const OPERATING_SYSTEMS: &[&str] = &["linux", "macos", "windows"];
+
+/// Editor commands, as found in `$VISUAL` or `$EDITOR`, and their templates.
+const EDITORS: &[(&str, &str)] = &[
+    ("atom", "atom"),
+    ("code", "visualstudiocode"),
+    ("codium", "visualstudiocode"),
+    ("emacs", "emacs"),
+    ("emacsclient", "emacs"),
+    ("gvim", "vim"),
+    ("idea", "jetbrains"),
+    ("kate", "kate"),
+    ("mvim", "vim"),
+    ("nvim", "vim"),
+    ("pycharm", "jetbrains"),
+    ("subl", "sublimetext"),
+    ("vi", "vim"),
+    ("vim", "vim"),
+    ("webstorm", "jetbrains"),
+];
+
+/// Editor directories at the root of the repository and their templates.
+const EDITOR_DIRS: &[(&str, &str)] = &[
+    (".idea", "jetbrains"),
+    (".vs", "visualstudio"),
+    (".vscode", "visualstudiocode"),
+];
+
+/// A template detected from the machine and the developer's setup rather
+/// than the project files, with a description of why.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Detection {
+    pub template: String,
+    pub reason: String,
+}
+
+/// Every template the environment can be detected as, for any operating
+/// system or editor.
+pub fn templates() -> Vec<&'static str> {
+    let mut templates: Vec<&str> = OPERATING_SYSTEMS
+        .iter()
+        .copied()
+        .chain(
+            EDITORS
+                .iter()
+                .chain(EDITOR_DIRS)
+                .map(|(_, template)| *template),
+        )
+        .collect();
+    templates.sort_unstable();
+    templates.dedup();
+    templates
+}
+
+/// Detects the templates for the current operating system, the editor in
+/// `$VISUAL` or `$EDITOR` and the editor directories among `entries`.
+pub fn detect<E: DirEntry>(entries: &[E]) -> Vec<Detection> {
+    let editor = ["VISUAL", "EDITOR"].iter().find_map(|var| {
+        env::var(var)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| (*var, value))
+    });
+
+    let root_dirs: Vec<OsString> = entries
+        .iter()
+        .filter(|entry| entry.is_dir() && entry.relative_path().components().count() == 1)
+        .map(|entry| entry.file_name())
+        .collect();
+
+    detect_from(env::consts::OS, editor, &root_dirs)
+}
+
+fn detect_from(os: &str, editor: Option<(&str, String)>, root_dirs: &[OsString]) -> Vec<Detection> {
+    let mut detections = Vec::new();
+    let mut add = |template: &str, reason: String| {
+        if !detections
+            .iter()
+            .any(|d: &Detection| d.template == template)
+        {
+            detections.push(Detection {
+                template: template.to_string(),
+                reason,
+            });
+        }
+    };
+
+    if OPERATING_SYSTEMS.contains(&os) {
+        add(os, format!("running on {}", os));
+    }
+
+    if let Some((var, value)) = editor {
+        let command = value.split_whitespace().next().unwrap_or_default();
+        let name = Path::new(command)
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if let Some((_, template)) = EDITORS.iter().find(|(editor, _)| *editor == name) {
+            add(template, format!("${} is {}", var, command));
+        }
+    }
+
+    for name in root_dirs {
+        if let Some((dir, template)) = EDITOR_DIRS.iter().find(|(dir, _)| name == *dir) {
+            add(template, format!("the repository has a {} directory", dir));
+        }
+    }
+
+    detections
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::{detect_from, templates};
+
+    #[test]
+    fn detects_os_editor_and_editor_dirs() {
+        let result: Vec<_> = detect_from(
+            "macos",
+            Some(("EDITOR", "/usr/bin/nvim -f".to_string())),
+            &[".vs".into(), "src".into()],
+        )
+        .into_iter()
+        .map(|detection| (detection.template, detection.reason))
+        .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                ("macos".to_string(), "running on macos".to_string()),
+                ("vim".to_string(), "$EDITOR is /usr/bin/nvim".to_string()),
+                (
+                    "visualstudio".to_string(),
+                    "the repository has a .vs directory".to_string()
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn lists_every_template_once() {
+        let result = templates();
+        assert!(["linux", "kate", "sublimetext", "visualstudio"]
+            .iter()
+            .all(|template| result.contains(template)));
+        assert_eq!(result.iter().filter(|t| **t == "jetbrains").count(), 1)
+    }
+}
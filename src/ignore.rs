@@ -1,14 +1,20 @@
-use crate::{config::Config, detector::Detectors};
-use anyhow::Result;
-use colored::Colorize;
+use crate::{
+    config::Config,
+    detector::{Detectors, Finding},
+    environment, managed, picker,
+    scanner::{self, Entry, Scanner, DEFAULT_MAX_DEPTH},
+    template,
+};
+use anyhow::{bail, Context, Result};
+use colored::{ColoredString, Colorize};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     env::current_dir,
     fmt::{write, Display},
-    fs::{read_dir, read_to_string, DirEntry, File},
+    fs::{read_to_string, File},
     hash::{Hash, Hasher},
     io::Write,
     path::{Path, PathBuf},
@@ -19,13 +25,21 @@ pub fn project_dirs() -> ProjectDirs {
         .expect("Could not find project directory.")
 }
 
+/// The default location of the cache, in the cache directory for the current
+/// platform.
+pub fn default_cache_dir() -> PathBuf {
+    project_dirs().cache_dir().into()
+}
+
 #[derive(Debug)]
 pub struct Core {
     server: String,
+    config_file: PathBuf,
     cache_dir: PathBuf,
     ignore_file: PathBuf,
-    detectors: Detectors,
     pub config: Option<Config>,
+    project: Option<Config>,
+    registry: Option<Config>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -42,6 +56,8 @@ pub enum Type {
     Normal(String),
     Alias(String),
     Template(String),
+    Patched(String),
+    Registry(String),
 }
 
 impl Display for Type {
@@ -50,6 +66,8 @@ impl Display for Type {
             Type::Normal(name) => write(f, format_args!("{}", name)),
             Type::Alias(name) => write(f, format_args!("{}", name.yellow())),
             Type::Template(name) => write(f, format_args!("{}", name.blue())),
+            Type::Patched(name) => write(f, format_args!("{}*", name.magenta())),
+            Type::Registry(name) => write(f, format_args!("{}", name.green())),
         }
     }
 }
@@ -81,9 +99,13 @@ impl Hash for Type {
 }
 
 impl Type {
-    fn inner(&self) -> &str {
+    pub fn inner(&self) -> &str {
         match self {
-            Type::Normal(name) | Type::Alias(name) | Type::Template(name) => name,
+            Type::Normal(name)
+            | Type::Alias(name)
+            | Type::Template(name)
+            | Type::Patched(name)
+            | Type::Registry(name) => name,
         }
     }
 
@@ -93,14 +115,151 @@ impl Type {
     }
 }
 
+/// Resolves names to their contents, expanding `#!include` directives in
+/// custom templates. Every template is only rendered once, even when it is
+/// included from several places.
+struct Renderer<'a> {
+    config: &'a Config,
+    ignore_file: &'a HashMap<Type, Language>,
+    variables: HashMap<String, String>,
+    rendered: HashSet<String>,
+    unresolved: BTreeMap<String, BTreeSet<String>>,
+    stack: Vec<String>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(
+        config: &'a Config,
+        ignore_file: &'a HashMap<Type, Language>,
+        variables: HashMap<String, String>,
+    ) -> Self {
+        Renderer {
+            config,
+            ignore_file,
+            variables,
+            rendered: HashSet::new(),
+            unresolved: BTreeMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Appends the contents of `name` to `result`, returns false if no
+    /// template, alias or normal template with that name exists.
+    fn render(&mut self, name: &str, result: &mut String) -> Result<bool> {
+        if let Some(path) = self.config.templates.get(name) {
+            if self.stack.iter().any(|n| n == name) {
+                bail!(
+                    "Include cycle detected: {} -> {}",
+                    self.stack.join(" -> "),
+                    name
+                );
+            }
+
+            if !self.rendered.insert(format!("template:{}", name)) {
+                return Ok(true);
+            }
+
+            self.stack.push(name.to_string());
+            let content = self.config.read_template(path)?;
+
+            let mut variables = self.variables.clone();
+            for (key, value) in content.lines().filter_map(template::default_directive) {
+                variables
+                    .entry(key.to_string())
+                    .or_insert_with(|| value.to_string());
+            }
+
+            for line in content.lines() {
+                if let Some(includes) = template::include_directive(line) {
+                    for include in includes {
+                        if !self.render(include, result)? {
+                            bail!("Unknown template {} included from {}", include, name);
+                        }
+                    }
+                } else if !template::is_directive(line) {
+                    let (line, unresolved) = template::substitute(line, &variables);
+                    if !unresolved.is_empty() {
+                        self.unresolved
+                            .entry(name.to_string())
+                            .or_default()
+                            .extend(unresolved);
+                    }
+
+                    result.push_str(&line);
+                    result.push('\n');
+                }
+            }
+            self.stack.pop();
+        } else if let Some(val) = self.config.aliases.get(name) {
+            for alias in val {
+                self.render_normal(alias, result);
+            }
+        } else {
+            return Ok(self.render_normal(name, result));
+        }
+
+        Ok(true)
+    }
+
+    /// Fails with a list of every placeholder that had no value, grouped by
+    /// the template it was found in.
+    fn check_unresolved(&self) -> Result<()> {
+        if self.unresolved.is_empty() {
+            return Ok(());
+        }
+
+        let missing: Vec<String> = self
+            .unresolved
+            .iter()
+            .map(|(template, names)| {
+                let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                format!("  {}: {}", template, names.join(", "))
+            })
+            .collect();
+
+        bail!(
+            "Unresolved variables, pass them with `--set key=value`:\n{}",
+            missing.join("\n")
+        )
+    }
+
+    fn render_normal(&mut self, name: &str, result: &mut String) -> bool {
+        match self.ignore_file.get(&Type::Normal(name.to_string())) {
+            Some(language) => {
+                if self.rendered.insert(format!("normal:{}", name)) {
+                    match self.config.patches.get(name) {
+                        Some(patch) => result.push_str(&patch.apply(&language.contents)),
+                        None => result.push_str(&language.contents),
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Formats a TOML value on a single line, using inline tables for tables.
+fn inline_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, inline_value(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
 impl Core {
     /// Creates a new instance of the `git-ignore` program. Thanks to
     /// `directories` we support crossplatform caching of our results, the cache
     /// directories works on macOS, Linux and Windows. See the documentation for
-    /// their locations.
-    pub fn new() -> Self {
-        let proj_dir = project_dirs();
-        let cache_dir: PathBuf = proj_dir.cache_dir().into();
+    /// their locations. The config file and cache directory can be overridden
+    /// with `config_file` and `cache_dir`.
+    pub fn new(config_file: PathBuf, cache_dir: PathBuf) -> Result<Self> {
         let ignore_file: PathBuf = [
             cache_dir
                 .to_str()
@@ -110,25 +269,43 @@ impl Core {
         .iter()
         .collect();
 
-        let config = Config::from_dir();
+        let config = Config::from_dir(&config_file)?;
+        let project = Config::from_project()?;
+        let server = [&project, &config]
+            .iter()
+            .find_map(|config| config.as_ref().and_then(|c| c.server.clone()))
+            .unwrap_or_else(|| "https://www.gitignore.io/api/list?format=json".into());
 
-        Core {
-            server: "https://www.gitignore.io/api/list?format=json".into(),
+        let mut core = Core {
+            server,
+            config_file,
             cache_dir,
             ignore_file,
-            detectors: Detectors::default(),
             config,
-        }
+            project,
+            registry: None,
+        };
+        core.registry = core.load_registries()?;
+
+        Ok(core)
     }
 
     /// Both updates and initializes `git-ignore`. Creates the cache directory
     /// if it doesn't exist and then downloads the templates from
     /// [gitignore.io](https://www.gitignore.io), saving them in the cache
     /// directory.
-    pub fn update(&self) -> Result<()> {
+    ///
+    /// Registries from git repositories are cloned or pulled into the cache as
+    /// well.
+    pub fn update(&mut self) -> Result<()> {
         self.create_dirs()?;
         self.fetch_gitignore()?;
 
+        for (name, registry) in &self.personal_config(false).registries {
+            registry.update(name, &self.cache_dir)?;
+        }
+        self.registry = self.load_registries()?;
+
         eprintln!("{}: Update successful", "Info".bold().green());
         Ok(())
     }
@@ -161,43 +338,627 @@ impl Core {
 
     /// Writes the `content` field for each entry in templates from `read_file`
     /// to `stdout`.
-    pub fn get_templates(&self, names: &[String], simple: bool) -> Result<()> {
-        let (aliases, templates) = match &self.config {
-            Some(config) if !simple => (config.aliases.clone(), config.templates.clone()),
-            _ => (HashMap::new(), HashMap::new()),
+    /// Placeholders in custom templates are replaced with `variables`, falling
+    /// back to the variables in the config and the defaults in the template.
+    /// The default templates from the config are added unless `no_defaults`.
+    pub fn get_templates(
+        &self,
+        names: &[String],
+        simple: bool,
+        no_defaults: bool,
+        variables: &[(String, String)],
+    ) -> Result<()> {
+        let config = self.effective_config(simple);
+
+        let mut names = names.to_vec();
+        let implicit = Core::implicit_defaults(&config, &names, no_defaults);
+        if !implicit.is_empty() {
+            eprintln!(
+                "{}: Adding default templates {}",
+                "Info".bold().green(),
+                implicit.join(", ")
+            );
+            names.extend(implicit);
+        }
+
+        let ignore_file = self.read_file()?;
+        println!(
+            "{}",
+            Core::render_templates(&config, &ignore_file, &names, variables)?
+        );
+        Ok(())
+    }
+
+    /// Lets the user pick templates, aliases and custom templates in the
+    /// terminal, previewing how each renders, and prints the picked ones. With
+    /// `write` they are written to the managed section of `.gitignore` in the
+    /// current directory instead.
+    pub fn pick_templates(&self, write: bool, no_defaults: bool) -> Result<()> {
+        let config = self.effective_config(false);
+        let names = self.all_names(false)?.into_iter().collect();
+        let ignore_file = self.read_file()?;
+        let preview = |name: &Type| {
+            Core::render_templates(&config, &ignore_file, &[name.inner().to_string()], &[])
+                .map(|preview| preview.trim_start().to_string())
+                .unwrap_or_else(|e| format!("{:#}", e))
+        };
+
+        let mut names = match picker::pick(names, preview)? {
+            Some(names) if !names.is_empty() => names,
+            _ => {
+                eprintln!("{}: Nothing picked", "Info".bold().green());
+                return Ok(());
+            }
+        };
+        if !write {
+            return self.get_templates(&names, false, no_defaults, &[]);
+        }
+
+        names.extend(Core::implicit_defaults(&config, &names, no_defaults));
+        let file = current_dir()?.join(".gitignore");
+        let existing = if file.exists() {
+            read_to_string(&file).with_context(|| format!("Could not read {}", file.display()))?
+        } else {
+            String::new()
         };
+        let contents = Core::render_templates(&config, &ignore_file, &names, &[])?;
+        let updated = managed::update(&existing, &contents)
+            .with_context(|| format!("Could not update {}", file.display()))?;
+
+        let mut out =
+            File::create(&file).with_context(|| format!("Could not write {}", file.display()))?;
+        out.write_all(updated.as_bytes())?;
+        println!("Wrote {} to {}", names.join(", "), ".gitignore".yellow());
 
+        Ok(())
+    }
+
+    /// Runs the detection separately for every subproject, the directories
+    /// below the current one containing a manifest, and writes the templates
+    /// detected for each to the managed section of its `.gitignore`. The plan
+    /// is printed first, and unless `yes` is given, confirmed before writing.
+    pub fn generate_per_directory(
+        &self,
+        max_depth: Option<usize>,
+        tracked: bool,
+        no_defaults: bool,
+        dry_run: bool,
+        yes: bool,
+    ) -> Result<()> {
+        let config = self.effective_config(false);
+        let (detectors, entries) = self.scan(max_depth, tracked, None)?;
+        let root = current_dir()?;
         let ignore_file = self.read_file()?;
+
+        let mut plan = Vec::new();
+        for (dir, entries) in scanner::subprojects(&entries) {
+            let mut names = self.existing_templates(detectors.detects(entries.as_slice()))?;
+            if names.is_empty() {
+                continue;
+            }
+            names.extend(Core::implicit_defaults(&config, &names, no_defaults));
+
+            let file = root.join(&dir).join(".gitignore");
+            let existing = if file.exists() {
+                Some(
+                    read_to_string(&file)
+                        .with_context(|| format!("Could not read {}", file.display()))?,
+                )
+            } else {
+                None
+            };
+            let contents = Core::render_templates(&config, &ignore_file, &names, &[])?;
+            let updated = managed::update(existing.as_deref().unwrap_or_default(), &contents)
+                .with_context(|| format!("Could not update {}", file.display()))?;
+
+            let action = match &existing {
+                None => "create",
+                Some(existing) if existing == &updated => "unchanged",
+                Some(existing) if existing.contains(managed::BEGIN) => "update",
+                Some(_) => "append to",
+            };
+            plan.push(Planned {
+                path: dir.join(".gitignore"),
+                file,
+                action,
+                names,
+                contents: updated,
+            });
+        }
+
+        if plan.is_empty() {
+            println!("No subprojects with templates found");
+            return Ok(());
+        }
+
+        println!("Plan:");
+        for planned in &plan {
+            println!(
+                "{} {} with {}",
+                planned.action,
+                planned.path.display().to_string().yellow(),
+                planned.names.join(", ")
+            );
+        }
+
+        plan.retain(|planned| planned.action != "unchanged");
+        if dry_run || plan.is_empty() {
+            return Ok(());
+        }
+        if !yes && !confirm(&format!("Write {} files?", plan.len()))? {
+            println!("Nothing written");
+            return Ok(());
+        }
+
+        for planned in plan {
+            let mut file = File::create(&planned.file)
+                .with_context(|| format!("Could not write {}", planned.file.display()))?;
+            file.write_all(planned.contents.as_bytes())?;
+            println!("Wrote {}", planned.path.display().to_string().yellow());
+        }
+
+        Ok(())
+    }
+
+    /// The default templates in the config that are not in `names` already,
+    /// none with `no_defaults`.
+    fn implicit_defaults(config: &Config, names: &[String], no_defaults: bool) -> Vec<String> {
+        if no_defaults {
+            return Vec::new();
+        }
+
+        config
+            .defaults
+            .iter()
+            .filter(|name| !names.contains(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Renders `names` into a single `.gitignore`, with the header, using the
+    /// templates parsed from `read_file`.
+    fn render_templates(
+        config: &Config,
+        ignore_file: &HashMap<Type, Language>,
+        names: &[String],
+        variables: &[(String, String)],
+    ) -> Result<String> {
+        let mut all_variables = config.variables.clone();
+        all_variables.extend(variables.iter().cloned());
+
+        let mut renderer = Renderer::new(config, ignore_file, all_variables);
         let mut result = String::new();
 
         for name in names {
-            if let Some(val) = templates.get(name) {
-                let template = Config::read_template(val)?;
-                result.push_str(&template);
-            } else if let Some(val) = aliases.get(name) {
-                for alias in val {
-                    if let Some(language) = ignore_file.get(&Type::Alias(alias.to_string())) {
-                        result.push_str(&language.contents);
-                    }
-                }
-            } else if let Some(language) = ignore_file.get(&Type::Normal(name.to_string())) {
-                result.push_str(&language.contents);
-            }
+            renderer.render(name, &mut result)?;
         }
+        renderer.check_unresolved()?;
 
         if !result.is_empty() {
-            let mut header = "\n\n### Created by https://www.gitignore.io".to_string();
+            let mut header = "\n\n### Created by https://www.gitignore.io\n".to_string();
             header.push_str(&result);
             result = header;
         }
 
-        println!("{}", result);
+        Ok(result)
+    }
+
+    /// Scans the current directory and its subdirectories up to `max_depth`,
+    /// falling back to the depth in the config, and returns the templates
+    /// matching the files found, using the built-in detectors and the ones
+    /// from the config.
+    ///
+    /// With `environment`, or if it is enabled in the config, templates for
+    /// the operating system and editor are added as well. With `tracked` only
+    /// the files tracked by git are scanned.
+    ///
+    /// Templates with a confidence below `threshold`, or the threshold in the
+    /// config, are left out, unless they are accepted when `interactive`.
+    pub fn autodetect_templates(
+        &self,
+        max_depth: Option<usize>,
+        environment: bool,
+        tracked: bool,
+        threshold: Option<f64>,
+        interactive: bool,
+    ) -> Result<Vec<String>> {
+        let (detectors, entries) = self.scan(max_depth, tracked, threshold)?;
+        let (confident, uncertain): (Vec<_>, Vec<_>) = detectors
+            .scores(entries.as_slice())
+            .into_iter()
+            .partition(|(_, score)| *score >= detectors.threshold());
+
+        let mut templates: Vec<String> = confident.into_iter().map(|(t, _)| t).collect();
+        for (template, score) in uncertain {
+            let question = format!("Add {} ({:.0}% confidence)?", template, score * 100.0);
+            if interactive && confirm(&question)? {
+                templates.push(template);
+            } else if !interactive {
+                eprintln!(
+                    "{}: Skipped {} with {:.0}% confidence, pass --interactive to confirm it",
+                    "Info".bold().green(),
+                    template,
+                    score * 100.0
+                );
+            }
+        }
+
+        if !self.environment_enabled(environment) {
+            return self.existing_templates(templates);
+        }
+
+        let from_environment: Vec<_> = environment::detect(entries.as_slice())
+            .into_iter()
+            .map(|detection| detection.template)
+            .filter(|template| !templates.contains(template))
+            .collect();
+        eprintln!(
+            "{}: Detected [{}] from the project and [{}] from the environment",
+            "Info".bold().green(),
+            templates.join(", "),
+            from_environment.join(", ")
+        );
+
+        templates.extend(from_environment);
+        self.existing_templates(templates)
+    }
+
+    /// Drops the detected templates that are not in the cache or the config,
+    /// with a warning, as they would make rendering fail.
+    fn existing_templates(&self, templates: Vec<String>) -> Result<Vec<String>> {
+        let names = self.all_names(false)?;
+        let (existing, missing): (Vec<_>, Vec<_>) = templates
+            .into_iter()
+            .partition(|template| names.contains(&Type::Normal(template.clone())));
+
+        for template in missing {
+            eprintln!(
+                "{}: Skipped detected template {}, it does not exist. Run `git ignore audit` to check the detectors",
+                "WARN".bold().red(),
+                template
+            );
+        }
+
+        Ok(existing)
+    }
+
+    /// Checks that every detector, built-in or from the config, and every
+    /// template the environment can be detected as adds a template or alias
+    /// that exists in the cache or the config. Fails if any does not, except
+    /// for disabled detectors, which are only reported.
+    pub fn audit_detectors(&self) -> Result<()> {
+        let detection = self.effective_config(false).detection;
+        let detectors = Detectors::from_config(&detection)?;
+        let names = self.all_names(false)?;
+        let origin = |template: &str| {
+            if detection.detectors.contains_key(template) {
+                "config"
+            } else {
+                "built-in"
+            }
+        };
+
+        // The template, where it comes from and whether it is enabled
+        let mut audited: Vec<(&str, String, bool)> = detectors
+            .templates()
+            .into_iter()
+            .map(|template| (template, origin(template).to_string(), true))
+            .collect();
+        audited.extend(
+            detectors
+                .disabled_templates()
+                .into_iter()
+                .map(|template| (template, format!("{}, disabled", origin(template)), false)),
+        );
+        audited.extend(
+            environment::templates()
+                .into_iter()
+                .map(|template| (template, "environment".to_string(), true)),
+        );
+
+        let total = audited.len();
+        let mut missing: Vec<_> = audited
+            .into_iter()
+            .filter(|(template, _, _)| !names.contains(&Type::Normal(template.to_string())))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        if missing.is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "All {} detectors and environment templates add existing templates",
+                    total
+                )
+                .green()
+            );
+            return Ok(());
+        }
+
+        println!("Detectors adding templates that do not exist:");
+        for (template, origin, _) in &missing {
+            println!("  {} {}", template.red(), format!("({})", origin).dimmed());
+        }
+
+        if missing.iter().any(|(_, _, enabled)| *enabled) {
+            bail!("Some detectors add templates that do not exist, run `git ignore -u` or update the detectors")
+        }
+
+        Ok(())
+    }
+
+    /// Prints which matcher and file made every detector fire, followed by
+    /// the templates detected from the environment and the files that almost
+    /// made the other detectors fire.
+    pub fn explain_detection(
+        &self,
+        max_depth: Option<usize>,
+        environment: bool,
+        tracked: bool,
+        threshold: Option<f64>,
+    ) -> Result<()> {
+        let (detectors, entries) = self.scan(max_depth, tracked, threshold)?;
+        let names = self.all_names(false)?;
+        let missing = |template: &str| {
+            if names.contains(&Type::Normal(template.to_string())) {
+                "".normal()
+            } else {
+                ", but the template does not exist and is skipped".red()
+            }
+        };
+        let (matched, near_misses): (Vec<_>, Vec<_>) = detectors
+            .explain(entries.as_slice())
+            .into_iter()
+            .partition(|explanation| explanation.finding == Finding::Matched);
+
+        if matched.is_empty() {
+            println!("No templates detected");
+        } else {
+            println!("Detected templates:");
+        }
+        for explanation in &matched {
+            println!(
+                "{} => {} matched {}{}{}",
+                explanation.template.bold(),
+                explanation.matcher,
+                explanation.path.display().to_string().yellow(),
+                weight(explanation.weight),
+                missing(&explanation.template)
+            );
+        }
+
+        if self.environment_enabled(environment) {
+            println!("\nDetected from the environment:");
+            for detection in environment::detect(entries.as_slice()) {
+                let duplicate = matched.iter().any(|e| e.template == detection.template);
+                println!(
+                    "{} => {}{}{}",
+                    detection.template.bold(),
+                    detection.reason,
+                    if duplicate {
+                        ", also detected from the project".dimmed()
+                    } else {
+                        "".normal()
+                    },
+                    missing(&detection.template)
+                );
+            }
+        }
+
+        if !near_misses.is_empty() {
+            println!("\nNear misses:");
+        }
+        for explanation in near_misses {
+            let reason = match explanation.finding {
+                Finding::Disabled => "but the detector is disabled".to_string(),
+                Finding::NearMiss(reason) => format!("but it {}", reason),
+                Finding::Suppressed(by) => format!("but {} suppresses it", by),
+                Finding::Matched => unreachable!(),
+            };
+            println!(
+                "{} => {} considered {}{}, {}",
+                explanation.template.bold(),
+                explanation.matcher,
+                explanation.path.display().to_string().yellow(),
+                weight(explanation.weight),
+                reason.dimmed()
+            );
+        }
+
         Ok(())
     }
 
-    pub fn autodetect_templates(&self) -> Result<Vec<String>> {
-        let entries: Vec<DirEntry> = read_dir(current_dir()?)?.map(|e| e.unwrap()).collect();
-        Ok(self.detectors.detects(entries.as_slice()))
+    /// Environment detection is enabled by the `--env` flag or the config.
+    fn environment_enabled(&self, flag: bool) -> bool {
+        flag || self
+            .effective_config(false)
+            .detection
+            .environment
+            .unwrap_or(false)
+    }
+
+    /// The detectors from the config and the entries to run them on, from
+    /// the git index if `tracked` or the config says so.
+    fn scan(
+        &self,
+        max_depth: Option<usize>,
+        tracked: bool,
+        threshold: Option<f64>,
+    ) -> Result<(Detectors, Vec<Entry>)> {
+        let mut detection = self.effective_config(false).detection;
+        if threshold.is_some() {
+            detection.threshold = threshold;
+        }
+        let max_depth = max_depth
+            .or(detection.max_depth)
+            .unwrap_or(DEFAULT_MAX_DEPTH);
+
+        let scanner = Scanner::new(max_depth, &detection.skip);
+        let entries = if tracked || detection.tracked.unwrap_or(false) {
+            scanner.tracked(&current_dir()?)?
+        } else {
+            scanner.scan(&current_dir()?)?
+        };
+        Ok((Detectors::from_config(&detection)?, entries))
+    }
+
+    /// Prints the user config merged with the project config, with `origin`
+    /// every entry is annotated with the file it comes from.
+    pub fn show_config(&self, origin: bool) -> Result<()> {
+        let effective = self.effective_config(false);
+        if !origin {
+            println!("{}", toml::to_string_pretty(&effective)?);
+            return Ok(());
+        }
+
+        let to_table = |config: Option<&Config>| -> Result<toml::value::Table> {
+            match config.map(toml::Value::try_from).transpose()? {
+                Some(toml::Value::Table(table)) => Ok(table),
+                _ => Ok(toml::value::Table::new()),
+            }
+        };
+        let user = to_table(self.config.as_ref())?;
+        let project = to_table(self.project.as_ref())?;
+        let registry = to_table(self.registry.as_ref())?;
+        let origin_of = |path: &[&str]| {
+            let has = |table: &toml::value::Table| {
+                let (first, rest) = path.split_first().expect("path is never empty");
+                rest.iter()
+                    .try_fold(table.get(*first), |value, part| {
+                        value.map(|value| value.get(part))
+                    })
+                    .flatten()
+                    .is_some()
+            };
+
+            let file = match (&self.project, &self.config) {
+                (Some(project_config), _) if has(&project) => project_config.path.display(),
+                (_, Some(user_config)) if has(&user) => user_config.path.display(),
+                _ if has(&registry) => return "# registry".dimmed(),
+                _ => return "# default".dimmed(),
+            };
+            format!("# {}", file).dimmed()
+        };
+
+        // The version is filled in when reading every config, so it has no
+        // origin, and empty sections have nothing to attribute
+        let (tables, values): (Vec<_>, Vec<_>) = to_table(Some(&effective))?
+            .into_iter()
+            .filter(|(key, value)| {
+                key != "version" && value.as_table().is_none_or(|table| !table.is_empty())
+            })
+            .partition(|(_, value)| value.is_table());
+
+        for (key, value) in &values {
+            println!("{} = {} {}", key.blue(), value, origin_of(&[key]));
+        }
+        if !values.is_empty() {
+            println!();
+        }
+
+        for (section, value) in tables {
+            println!("[{}]", section.bold().green());
+            if let toml::Value::Table(entries) = value {
+                for (key, value) in entries {
+                    println!(
+                        "{} = {} {}",
+                        key.blue(),
+                        inline_value(&value),
+                        origin_of(&[&section, &key])
+                    );
+                }
+            }
+            println!();
+        }
+
+        Ok(())
+    }
+
+    /// Prints the value of a dotted `key` in the user config merged with the
+    /// project config.
+    pub fn get_config(&self, key: &str) -> Result<()> {
+        let effective = toml::Value::try_from(self.effective_config(false))?;
+        let value = key
+            .split('.')
+            .try_fold(&effective, |value, part| value.get(part))
+            .with_context(|| format!("No value for {} in the config", key))?;
+
+        match value {
+            toml::Value::String(value) => println!("{}", value),
+            toml::Value::Table(_) => print!("{}", toml::to_string_pretty(value)?),
+            value => println!("{}", value),
+        }
+
+        Ok(())
+    }
+
+    /// Combines the registries, user and project config, in increasing order
+    /// of precedence. Returns an empty config if `simple` is true.
+    fn effective_config(&self, simple: bool) -> Config {
+        let personal = self.personal_config(simple);
+        let mut config = match &self.registry {
+            Some(registry) if !simple => Config {
+                path: personal.path.clone(),
+                ..registry.clone()
+            },
+            _ => return personal,
+        };
+        config.merge(&personal);
+
+        config
+    }
+
+    /// Combines the user and project config, the project config takes
+    /// precedence. Returns an empty config if `simple` is true.
+    fn personal_config(&self, simple: bool) -> Config {
+        let mut config = Config {
+            path: self.config_file.clone(),
+            ..Default::default()
+        };
+        if simple {
+            return config;
+        }
+
+        if let Some(user) = &self.config {
+            let mut user = user.clone();
+            if let Some(dir) = self.config_file.parent() {
+                user.resolve_registries(dir);
+            }
+            config.merge(&user);
+        }
+        if let Some(project) = &self.project {
+            config.merge(project);
+        }
+
+        config
+    }
+
+    /// Reads the manifests of all registries in the user and project config
+    /// into a single config, registries that have not been fetched yet are
+    /// skipped.
+    fn load_registries(&self) -> Result<Option<Config>> {
+        let registries = self.personal_config(false).registries;
+        if registries.is_empty() {
+            return Ok(None);
+        }
+
+        let mut names: Vec<&String> = registries.keys().collect();
+        names.sort_unstable();
+
+        let mut config = Config::default();
+        for name in names {
+            match registries[name].load(name, &self.cache_dir)? {
+                Some(registry) => config.merge(&registry),
+                None => eprintln!(
+                    "{}: Registry {} has not been fetched, pass '-u' to fetch it",
+                    "Warning".bold().red(),
+                    name.green()
+                ),
+            }
+        }
+
+        Ok(Some(config))
     }
 
     fn all_names(&self, simple: bool) -> Result<HashSet<Type>> {
@@ -207,12 +968,16 @@ impl Core {
             return Ok(templates.keys().cloned().collect());
         }
 
-        let config_names = match &self.config {
-            Some(config) => config.names(),
-            _ => vec![],
-        };
+        let config_names = self.personal_config(simple).names();
+        let registry_names = self.registry.iter().flat_map(|registry| {
+            registry
+                .names()
+                .into_iter()
+                .map(|name| Type::Registry(name.inner().to_string()))
+        });
 
         let mut combined: HashSet<Type> = config_names.into_iter().collect();
+        combined.extend(registry_names);
         combined.extend(templates.keys().cloned());
 
         Ok(combined)
@@ -249,9 +1014,15 @@ impl Core {
     /// some other stuff).
     fn read_file(&self) -> Result<HashMap<Type, Language>> {
         let file = Path::new(&self.ignore_file);
-        let file = read_to_string(file)?;
+        let content = read_to_string(file).with_context(|| {
+            format!(
+                "Could not read {}, run `git ignore -u` to fetch the templates",
+                file.display()
+            )
+        })?;
 
-        let result: HashMap<String, Language> = serde_json::from_str(&file)?;
+        let result: HashMap<String, Language> = serde_json::from_str(&content)
+            .with_context(|| format!("Could not parse {}", file.display()))?;
         let result: HashMap<Type, Language> = result
             .into_iter()
             .map(|(k, v)| (Type::Normal(k), v))
@@ -260,3 +1031,180 @@ impl Core {
         Ok(result)
     }
 }
+
+/// A `.gitignore` to write in `Core::generate_per_directory`.
+struct Planned {
+    /// Path relative to the current directory, for display
+    path: PathBuf,
+    file: PathBuf,
+    action: &'static str,
+    names: Vec<String>,
+    contents: String,
+}
+
+/// Shows the weight of weak matchers, nothing for the others.
+fn weight(weight: f64) -> ColoredString {
+    if weight < 1.0 {
+        format!(" (weight {:.0}%)", weight * 100.0).dimmed()
+    } else {
+        "".normal()
+    }
+}
+
+/// Asks a yes or no question on `stderr`, anything but yes is a no.
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        ignore::{Core, Language, Renderer, Type},
+        testing::TempDir,
+    };
+    use std::collections::HashMap;
+
+    /// A config in `dir` with the given custom templates, named after their
+    /// file.
+    fn config_with(dir: &TempDir, templates: &[(&str, &str)]) -> Config {
+        let mut config = dir.config();
+        for (name, contents) in templates {
+            dir.write(&format!("templates/{}", name), contents);
+            config.templates.insert(name.to_string(), name.to_string());
+        }
+
+        config
+    }
+
+    fn ignore_file(names: &[&str]) -> HashMap<Type, Language> {
+        names
+            .iter()
+            .map(|name| {
+                let language = Language {
+                    key: name.to_string(),
+                    name: name.to_string(),
+                    file_name: format!("{}.gitignore", name),
+                    contents: format!("\n### {} ###\n", name),
+                };
+                (Type::Normal(name.to_string()), language)
+            })
+            .collect()
+    }
+
+    /// A `Core` using `config`, with a cached `ignore.json` holding `names`.
+    fn core_with(dir: &TempDir, config: Config, names: &[&str]) -> Core {
+        let languages: HashMap<String, Language> = ignore_file(names)
+            .into_iter()
+            .map(|(name, language)| (name.inner().to_string(), language))
+            .collect();
+        let ignore_file = dir.write("ignore.json", &serde_json::to_string(&languages).unwrap());
+
+        Core {
+            server: String::new(),
+            config_file: config.path.clone(),
+            cache_dir: dir.path().to_path_buf(),
+            ignore_file,
+            config: Some(config),
+            project: None,
+            registry: None,
+        }
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let dir = TempDir::new("cycle");
+        let config = config_with(
+            &dir,
+            &[("a", "#!include b\n/a\n"), ("b", "#!include a\n/b\n")],
+        );
+        let ignore_file = ignore_file(&[]);
+
+        let mut renderer = Renderer::new(&config, &ignore_file, HashMap::new());
+        let result = renderer.render("a", &mut String::new());
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Include cycle detected: a -> b -> a"
+        )
+    }
+
+    #[test]
+    fn renders_templates_included_twice_once() {
+        let dir = TempDir::new("twice");
+        let config = config_with(
+            &dir,
+            &[
+                ("docs", "/docs\n"),
+                ("a", "#!include docs rust\n/a\n"),
+                ("b", "#!include docs rust\n/b\n"),
+            ],
+        );
+        let ignore_file = ignore_file(&["rust"]);
+
+        let mut renderer = Renderer::new(&config, &ignore_file, HashMap::new());
+        let mut result = String::new();
+        for name in ["a", "b"] {
+            assert!(renderer.render(name, &mut result).unwrap());
+        }
+
+        assert_eq!(result, "/docs\n\n### rust ###\n/a\n/b\n")
+    }
+
+    #[test]
+    fn renders_header_on_its_own_line() {
+        let dir = TempDir::new("header");
+        let config = config_with(&dir, &[("docs", "/docs\n")]);
+        let ignore_file = ignore_file(&["rust"]);
+
+        let names = ["docs".to_string(), "rust".to_string()];
+        let result = Core::render_templates(&config, &ignore_file, &names, &[]).unwrap();
+
+        assert_eq!(
+            result,
+            "\n\n### Created by https://www.gitignore.io\n/docs\n\n### rust ###\n"
+        )
+    }
+
+    #[test]
+    fn lists_registry_names_without_colors() {
+        let dir = TempDir::new("registry-names");
+        let mut core = core_with(&dir, dir.config(), &["rust"]);
+        let mut registry = Config::default();
+        registry
+            .templates
+            .insert("shared".to_string(), "shared.txt".to_string());
+        core.registry = Some(registry);
+
+        colored::control::set_override(true);
+        let names = core.all_names(false).unwrap();
+        colored::control::unset_override();
+
+        assert!(matches!(
+            names.get(&Type::Normal("shared".to_string())),
+            Some(Type::Registry(name)) if name == "shared"
+        ));
+    }
+
+    #[test]
+    fn adds_default_templates_once() {
+        let dir = TempDir::new("defaults");
+        let mut config = dir.config();
+        config.defaults = vec!["macos".to_string(), "rust".to_string()];
+        let core = core_with(&dir, config, &["macos", "rust"]);
+        let names = ["rust".to_string()];
+
+        let defaults = Core::implicit_defaults(&core.effective_config(false), &names, false);
+        let no_defaults = Core::implicit_defaults(&core.effective_config(false), &names, true);
+        let simple = Core::implicit_defaults(&core.effective_config(true), &names, false);
+
+        assert_eq!(defaults, vec!["macos"]);
+        assert!(no_defaults.is_empty());
+        assert!(simple.is_empty());
+    }
+}
@@ -1,17 +1,27 @@
 #![doc = include_str!("../README.md")]
 #![forbid(unsafe_code)]
 
+mod bundle;
 mod cli;
 mod config;
 mod detector;
+mod environment;
 mod ignore;
+mod managed;
+mod manifest;
+mod picker;
+mod registry;
+mod scanner;
+mod template;
+#[cfg(test)]
+mod testing;
 
 use anyhow::Result;
-use clap::{IntoApp, Parser};
-use cli::{print_completion, AliasCmd, Cmds, TemplateCmd, CLI};
+use clap::{ErrorKind, IntoApp, Parser};
+use cli::{print_completion, AliasCmd, Cmds, ConfigCmd, PatchCmd, TemplateCmd, CLI};
 use colored::Colorize;
-use config::Config;
-use ignore::Core;
+use config::{default_config_file, Config};
+use ignore::{default_cache_dir, Core};
 use std::collections::HashSet;
 
 macro_rules! config_or {
@@ -29,9 +39,9 @@ macro_rules! config_or {
 
         return Ok(());
     }};
-    ($sel:ident, $fun:ident, $name:expr) => {{
+    ($sel:ident, mut $fun:ident, $($args:expr),+) => {{
         if let Some(mut config) = $sel.config {
-            config.$fun($name)?;
+            config.$fun($($args),+)?;
         } else {
             eprintln!(
                 "{}",
@@ -43,9 +53,9 @@ macro_rules! config_or {
 
         return Ok(());
     }};
-    ($sel:ident, $fun:ident, $name:expr, $vals:expr) => {{
-        if let Some(mut config) = $sel.config {
-            config.$fun($name, $vals)?;
+    ($sel:ident, $fun:ident, $($args:expr),+) => {{
+        if let Some(config) = $sel.config {
+            config.$fun($($args),+)?;
         } else {
             eprintln!(
                 "{}",
@@ -59,26 +69,122 @@ macro_rules! config_or {
     }};
 }
 
+/// Exits with an error if arguments for generating a `.gitignore` are passed
+/// along with a subcommand, as they would be ignored.
+fn check_subcommand_args(opt: &CLI) {
+    let generate_args = opt.list
+        || opt.update
+        || opt.simple
+        || opt.no_defaults
+        || opt.auto
+        || opt.depth.is_some()
+        || !opt.set.is_empty()
+        || !opt.templates.is_empty();
+
+    if opt.cmd.is_some() && generate_args {
+        CLI::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "Only --config and --cache-dir can be used together with a subcommand",
+            )
+            .exit();
+    }
+}
+
 fn main() -> Result<()> {
     let opt = CLI::parse();
-    let app = Core::new();
+    check_subcommand_args(&opt);
+    let config_file = opt.config.unwrap_or_else(default_config_file);
+    let cache_dir = opt.cache_dir.unwrap_or_else(default_cache_dir);
+
+    // These work without loading the config, or on the config file directly,
+    // even when it cannot be parsed
+    let cmd = match opt.cmd {
+        Some(Cmds::Init { force }) => return Config::create(config_file, force),
+        Some(Cmds::Config(ConfigCmd::Edit)) => return Config::edit(&config_file, &cache_dir),
+        Some(Cmds::Config(ConfigCmd::Validate)) => {
+            return Config::validate(&config_file, &cache_dir)
+        }
+        Some(Cmds::Completion { shell }) => {
+            let mut app = CLI::command();
+            print_completion(shell, &mut app);
+            return Ok(());
+        }
+        cmd => cmd,
+    };
 
-    match opt.cmd {
-        Some(Cmds::Init { force }) => return Config::create(force),
+    let mut app = Core::new(config_file, cache_dir)?;
+
+    match cmd {
         Some(Cmds::Alias(cmd)) => match cmd {
             AliasCmd::List => config_or!(app, list_aliases),
-            AliasCmd::Add { name, aliases } => config_or!(app, add_alias, name, aliases),
-            AliasCmd::Remove { name } => config_or!(app, remove_alias, &name),
+            AliasCmd::Add { name, aliases } => config_or!(app, mut add_alias, name, aliases),
+            AliasCmd::Remove { name } => config_or!(app, mut remove_alias, &name),
         },
         Some(Cmds::Template(cmd)) => match cmd {
             TemplateCmd::List => config_or!(app, list_templates),
-            TemplateCmd::Add { name, file_name } => config_or!(app, add_template, name, file_name),
-            TemplateCmd::Remove { name } => config_or!(app, remove_template, &name),
+            TemplateCmd::Add {
+                name,
+                file_name,
+                force,
+            } => config_or!(app, mut add_template, name, file_name, force),
+            TemplateCmd::Remove {
+                name,
+                delete,
+                archive,
+            } => config_or!(app, mut remove_template, &name, delete, archive),
+            TemplateCmd::Check => config_or!(app, check_templates),
         },
-        Some(Cmds::Completion { shell }) => {
-            let mut app = CLI::command();
-            print_completion(shell, &mut app);
-            return Ok(());
+        Some(Cmds::Patch(cmd)) => match cmd {
+            PatchCmd::List => config_or!(app, list_patches),
+            PatchCmd::Add {
+                name,
+                remove,
+                append,
+            } => config_or!(app, mut add_patch, name, remove, append),
+            PatchCmd::Remove { name } => config_or!(app, mut remove_patch, &name),
+        },
+        Some(Cmds::Detect {
+            depth,
+            env,
+            tracked,
+            threshold,
+        }) => {
+            if !app.cache_exists() {
+                app.update()?;
+            }
+            return app.explain_detection(depth, env, tracked, threshold);
+        }
+        Some(Cmds::Audit) => {
+            if !app.cache_exists() {
+                app.update()?;
+            }
+            return app.audit_detectors();
+        }
+        Some(Cmds::Pick { write, no_defaults }) => {
+            if !app.cache_exists() {
+                app.update()?;
+            }
+            return app.pick_templates(write, no_defaults);
+        }
+        Some(Cmds::Monorepo {
+            depth,
+            tracked,
+            no_defaults,
+            dry_run,
+            yes,
+        }) => {
+            if !app.cache_exists() {
+                app.update()?;
+            }
+            return app.generate_per_directory(depth, tracked, no_defaults, dry_run, yes);
+        }
+        Some(Cmds::Export { file }) => config_or!(app, export, file),
+        Some(Cmds::Import { file, on_conflict }) => config_or!(app, mut import, file, on_conflict),
+        Some(Cmds::Config(ConfigCmd::Show { origin })) => return app.show_config(origin),
+        Some(Cmds::Config(ConfigCmd::Get { key })) => return app.get_config(&key),
+        Some(Cmds::Config(ConfigCmd::Set { key, value })) => {
+            config_or!(app, mut set, &key, &value)
         }
         _ => {}
     };
@@ -100,7 +206,13 @@ fn main() -> Result<()> {
 
     let mut all_templates: HashSet<String> = opt.templates.into_iter().collect();
     if opt.auto {
-        for template in app.autodetect_templates()? {
+        for template in app.autodetect_templates(
+            opt.depth,
+            opt.env,
+            opt.tracked,
+            opt.threshold,
+            opt.interactive,
+        )? {
             all_templates.insert(template);
         }
     }
@@ -117,8 +229,40 @@ fn main() -> Result<()> {
         let mut app = CLI::command();
         app.print_help()?;
     } else {
-        app.get_templates(templates.as_slice(), opt.simple)?;
+        app.get_templates(templates.as_slice(), opt.simple, opt.no_defaults, &opt.set)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Cmds, CLI};
+    use clap::Parser;
+    use std::{env, path::PathBuf};
+
+    #[test]
+    fn reads_config_and_cache_dir_from_env_and_arguments() {
+        env::set_var("GIT_IGNORE_CONFIG", "/env/config.toml");
+        env::set_var("GIT_IGNORE_CACHE_DIR", "/env/cache");
+        let from_env = CLI::try_parse_from(["git-ignore", "audit"]);
+        let before = CLI::try_parse_from(["git-ignore", "--config", "/before.toml", "audit"]);
+        let after = CLI::try_parse_from(["git-ignore", "audit", "--cache-dir", "/after"]);
+        env::remove_var("GIT_IGNORE_CONFIG");
+        env::remove_var("GIT_IGNORE_CACHE_DIR");
+
+        let from_env = from_env.unwrap();
+        assert!(matches!(from_env.cmd, Some(Cmds::Audit)));
+        assert_eq!(from_env.config, Some(PathBuf::from("/env/config.toml")));
+        assert_eq!(from_env.cache_dir, Some(PathBuf::from("/env/cache")));
+
+        let before = before.unwrap();
+        assert!(matches!(before.cmd, Some(Cmds::Audit)));
+        assert_eq!(before.config, Some(PathBuf::from("/before.toml")));
+        assert_eq!(before.cache_dir, Some(PathBuf::from("/env/cache")));
+
+        let after = after.unwrap();
+        assert_eq!(after.config, Some(PathBuf::from("/env/config.toml")));
+        assert_eq!(after.cache_dir, Some(PathBuf::from("/after")));
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+
+/// The line starting the part of a `.gitignore` that `git ignore` manages.
+pub const BEGIN: &str =
+    "# --- git-ignore: start of managed section, changes here are overwritten ---";
+/// The line ending the managed part of a `.gitignore`.
+pub const END: &str = "# --- git-ignore: end of managed section ---";
+
+/// Replaces the managed section of `existing` with `section`, appending the
+/// section if there is none yet. Everything outside the markers is kept.
+pub fn update(existing: &str, section: &str) -> Result<String> {
+    let block = format!("{}\n{}\n{}", BEGIN, section.trim(), END);
+    let lines: Vec<&str> = existing.lines().collect();
+    let begin = lines.iter().position(|line| line.trim_end() == BEGIN);
+    let end = lines.iter().position(|line| line.trim_end() == END);
+
+    let result = match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => {
+            let mut result: Vec<&str> = lines[..begin].to_vec();
+            result.push(&block);
+            result.extend(&lines[end + 1..]);
+            result.join("\n")
+        }
+        (None, None) if existing.trim().is_empty() => block,
+        (None, None) => format!("{}\n\n{}", existing.trim_end(), block),
+        _ => bail!("The managed section is missing its start or end marker"),
+    };
+
+    Ok(result + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::managed::{update, BEGIN, END};
+
+    #[test]
+    fn replaces_only_the_managed_section() {
+        let existing = format!("/secrets\n{}\nold\n{}\n!keep\n", BEGIN, END);
+        let result = update(&existing, "new\n").unwrap();
+        assert_eq!(
+            result,
+            format!("/secrets\n{}\nnew\n{}\n!keep\n", BEGIN, END)
+        );
+    }
+
+    #[test]
+    fn appends_the_managed_section() {
+        let result = update("/secrets\n", "new").unwrap();
+        assert_eq!(result, format!("/secrets\n\n{}\nnew\n{}\n", BEGIN, END));
+    }
+}
@@ -0,0 +1,229 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// The manifests `dependencies` can read.
+pub const SUPPORTED: &[&str] = &[
+    "package.json",
+    "composer.json",
+    "Cargo.toml",
+    "pyproject.toml",
+    "requirements.txt",
+    "Gemfile",
+    "pubspec.yaml",
+    "pubspec.yml",
+];
+
+static GEM: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^\s*gem\s+["']([^"']+)["']"#).expect("Invalid Gemfile regex"));
+
+/// Returns the names of the dependencies declared in a package manifest, or
+/// `None` if `file_name` is not a known manifest or it cannot be parsed.
+pub fn dependencies(file_name: &str, contents: &str) -> Option<HashSet<String>> {
+    match file_name {
+        "package.json" => json_keys(
+            contents,
+            &[
+                "dependencies",
+                "devDependencies",
+                "peerDependencies",
+                "optionalDependencies",
+            ],
+        ),
+        "composer.json" => json_keys(contents, &["require", "require-dev"]),
+        "Cargo.toml" => cargo(contents),
+        "pyproject.toml" => pyproject(contents),
+        "requirements.txt" => Some(requirements(contents)),
+        "Gemfile" => Some(gemfile(contents)),
+        "pubspec.yaml" | "pubspec.yml" => Some(pubspec(contents)),
+        _ => None,
+    }
+}
+
+/// Collects the keys of the given top level objects of a JSON document.
+fn json_keys(contents: &str, sections: &[&str]) -> Option<HashSet<String>> {
+    let value: serde_json::Value = serde_json::from_str(contents).ok()?;
+
+    Some(
+        sections
+            .iter()
+            .filter_map(|section| value.get(section)?.as_object())
+            .flat_map(|object| object.keys().cloned())
+            .collect(),
+    )
+}
+
+/// Collects the keys of the tables at the given dotted paths of a TOML document.
+fn toml_keys(value: &toml::Value, paths: &[&str]) -> HashSet<String> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            path.split('.')
+                .try_fold(value, |value, key| value.get(key))?
+                .as_table()
+        })
+        .flat_map(|table| table.keys().cloned())
+        .collect()
+}
+
+fn cargo(contents: &str) -> Option<HashSet<String>> {
+    let value: toml::Value = toml::from_str(contents).ok()?;
+
+    Some(toml_keys(
+        &value,
+        &[
+            "dependencies",
+            "dev-dependencies",
+            "build-dependencies",
+            "workspace.dependencies",
+        ],
+    ))
+}
+
+/// Reads both PEP 621 `[project]` dependencies and Poetry's tables, names are
+/// lowercased as Python package names are case insensitive.
+fn pyproject(contents: &str) -> Option<HashSet<String>> {
+    let value: toml::Value = toml::from_str(contents).ok()?;
+    let mut dependencies: HashSet<String> = toml_keys(
+        &value,
+        &["tool.poetry.dependencies", "tool.poetry.dev-dependencies"],
+    )
+    .into_iter()
+    .map(|name| name.to_lowercase())
+    .collect();
+
+    let project = value.get("project");
+    let required = project
+        .and_then(|project| project.get("dependencies"))
+        .and_then(|dependencies| dependencies.as_array());
+    let optional = project
+        .and_then(|project| project.get("optional-dependencies"))
+        .and_then(|dependencies| dependencies.as_table())
+        .into_iter()
+        .flat_map(|table| table.values())
+        .filter_map(|dependencies| dependencies.as_array());
+    let groups = value
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .and_then(|poetry| poetry.get("group"))
+        .and_then(|group| group.as_table())
+        .into_iter()
+        .flat_map(|table| table.values())
+        .filter_map(|group| group.get("dependencies")?.as_table());
+
+    for requirements in required.into_iter().chain(optional) {
+        dependencies.extend(
+            requirements
+                .iter()
+                .filter_map(|requirement| requirement.as_str())
+                .filter_map(requirement_name),
+        );
+    }
+    for table in groups {
+        dependencies.extend(table.keys().map(|name| name.to_lowercase()));
+    }
+
+    Some(dependencies)
+}
+
+fn requirements(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.starts_with('-'))
+        .filter_map(requirement_name)
+        .collect()
+}
+
+/// The package name at the start of a PEP 508 requirement like
+/// `django>=4.0; python_version > "3.8"`.
+fn requirement_name(requirement: &str) -> Option<String> {
+    let name: String = requirement
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_lowercase())
+    }
+}
+
+fn gemfile(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| GEM.captures(line))
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Reads the keys of the `dependencies` and `dev_dependencies` sections line
+/// by line, which is enough for the block style used by `pubspec.yaml`.
+fn pubspec(contents: &str) -> HashSet<String> {
+    let mut dependencies = HashSet::new();
+    let mut in_dependencies = false;
+    let mut indent = None;
+
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let line_indent = line.len() - trimmed.len();
+        if line_indent == 0 {
+            let section = trimmed.trim_end();
+            in_dependencies = section == "dependencies:" || section == "dev_dependencies:";
+            indent = None;
+            continue;
+        }
+
+        if !in_dependencies || *indent.get_or_insert(line_indent) != line_indent {
+            continue;
+        }
+        if let Some((name, _)) = trimmed.split_once(':') {
+            dependencies.insert(name.trim().to_string());
+        }
+    }
+
+    dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::dependencies;
+
+    #[test]
+    fn reads_pyproject_dependencies() {
+        let contents = r#"
+            [project]
+            dependencies = ["Django>=4.2", "requests"]
+
+            [tool.poetry.group.dev.dependencies]
+            pytest = "^7"
+        "#;
+
+        let mut result: Vec<_> = dependencies("pyproject.toml", contents)
+            .unwrap()
+            .into_iter()
+            .collect();
+        result.sort_unstable();
+        assert_eq!(result, vec!["django", "pytest", "requests"])
+    }
+
+    #[test]
+    fn reads_pubspec_dependencies() {
+        let contents = "name: app\n\
+                        dependencies:\n  flutter:\n    sdk: flutter\n  http: ^1.0.0\n\
+                        flutter:\n  uses-material-design: true\n";
+
+        let mut result: Vec<_> = dependencies("pubspec.yaml", contents)
+            .unwrap()
+            .into_iter()
+            .collect();
+        result.sort_unstable();
+        assert_eq!(result, vec!["flutter", "http"])
+    }
+}
@@ -0,0 +1,268 @@
+use crate::ignore::Type;
+use anyhow::Result;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{read, Event, KeyCode, KeyModifiers},
+    execute, queue,
+    style::{Print, Stylize},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::{
+    collections::HashMap,
+    io::{stderr, Stderr, Write},
+};
+
+/// Lets the user pick any number of `names` in the terminal, filtering them
+/// by typing and showing the `preview` of the one under the cursor. Returns
+/// the picked names in the order they were picked, or `None` if cancelled.
+///
+/// Tab toggles a name, Enter accepts, picking the name under the cursor if
+/// none are picked, and Esc cancels.
+pub fn pick<F: FnMut(&Type) -> String>(
+    names: Vec<Type>,
+    preview: F,
+) -> Result<Option<Vec<String>>> {
+    let _terminal = Terminal::enter()?;
+    Picker::new(names, preview).run()
+}
+
+/// The indices of the `names` matching `query`, best matches first.
+fn filter(names: &[Type], query: &str) -> Vec<usize> {
+    let matcher = SkimMatcherV2::default();
+    let mut matches: Vec<(i64, usize)> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let score = matcher.fuzzy_match(name.inner(), query)?;
+            Some((score, index))
+        })
+        .collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score).then_with(|| names[*a].cmp(&names[*b]))
+    });
+
+    matches.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Puts the terminal in raw mode on an alternate screen, restoring it when
+/// dropped, even when the picker fails.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(stderr(), EnterAlternateScreen, Hide)?;
+        // Colors go to the terminal even when stdout is piped
+        colored::control::set_override(true);
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        colored::control::unset_override();
+        let _ = execute!(stderr(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+struct Picker<F> {
+    names: Vec<Type>,
+    preview: F,
+    previews: HashMap<usize, String>,
+    query: String,
+    matches: Vec<usize>,
+    picked: Vec<usize>,
+    cursor: usize,
+    offset: usize,
+}
+
+impl<F: FnMut(&Type) -> String> Picker<F> {
+    fn new(mut names: Vec<Type>, preview: F) -> Self {
+        names.sort_unstable();
+        let matches = (0..names.len()).collect();
+
+        Picker {
+            names,
+            preview,
+            previews: HashMap::new(),
+            query: String::new(),
+            matches,
+            picked: Vec::new(),
+            cursor: 0,
+            offset: 0,
+        }
+    }
+
+    fn run(mut self) -> Result<Option<Vec<String>>> {
+        loop {
+            self.draw(&mut stderr())?;
+
+            let key = match read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if control => return Ok(None),
+                KeyCode::Enter => {
+                    if self.picked.is_empty() {
+                        self.picked.extend(self.matches.get(self.cursor));
+                    }
+                    let picked = self
+                        .picked
+                        .iter()
+                        .map(|index| self.names[*index].inner().to_string())
+                        .collect();
+                    return Ok(Some(picked));
+                }
+                KeyCode::Tab => {
+                    if let Some(index) = self.matches.get(self.cursor).copied() {
+                        match self.picked.iter().position(|picked| *picked == index) {
+                            Some(position) => {
+                                self.picked.remove(position);
+                            }
+                            None => self.picked.push(index),
+                        }
+                        self.move_cursor(1);
+                    }
+                }
+                KeyCode::Up => self.move_cursor(-1),
+                KeyCode::Char('p') if control => self.move_cursor(-1),
+                KeyCode::Down => self.move_cursor(1),
+                KeyCode::Char('n') if control => self.move_cursor(1),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) if !control => {
+                    self.query.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn refilter(&mut self) {
+        self.matches = filter(&self.names, &self.query);
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(by).min(last);
+    }
+
+    fn draw(&mut self, out: &mut Stderr) -> Result<()> {
+        let (width, height) = size()?;
+        let (width, height) = (width as usize, height as usize);
+        let rows = height.saturating_sub(2);
+        let list_width = (width / 3).max(20).min(width);
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if rows > 0 && self.cursor >= self.offset + rows {
+            self.offset = self.cursor + 1 - rows;
+        }
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        queue!(out, Print("> ".bold()), Print(&self.query))?;
+        queue!(
+            out,
+            MoveTo(0, 1),
+            Print(
+                format!(
+                    "{}/{} templates, {} picked, tab to pick, enter to accept, esc to cancel",
+                    self.matches.len(),
+                    self.names.len(),
+                    self.picked.len()
+                )
+                .dark_grey()
+            )
+        )?;
+
+        for (row, index) in self.matches.iter().skip(self.offset).take(rows).enumerate() {
+            let name = &self.names[*index];
+            let marker = match (
+                self.offset + row == self.cursor,
+                self.picked.contains(index),
+            ) {
+                (true, true) => ">*",
+                (true, false) => "> ",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            // Names too long for the list are shown without their color
+            let label = if name.inner().chars().count() + 3 > list_width {
+                name.inner()
+                    .chars()
+                    .take(list_width.saturating_sub(3))
+                    .collect()
+            } else {
+                name.to_string()
+            };
+
+            queue!(
+                out,
+                MoveTo(0, (row + 2) as u16),
+                Print(marker.bold()),
+                Print(" "),
+                Print(label)
+            )?;
+        }
+
+        if let Some(index) = self.matches.get(self.cursor).copied() {
+            if !self.previews.contains_key(&index) {
+                let preview = (self.preview)(&self.names[index]);
+                self.previews.insert(index, preview);
+            }
+            let preview = &self.previews[&index];
+            let preview_width = width.saturating_sub(list_width + 3);
+
+            for (row, line) in preview.lines().take(rows).enumerate() {
+                let line: String = line
+                    .replace('\t', "    ")
+                    .chars()
+                    .take(preview_width)
+                    .collect();
+                queue!(
+                    out,
+                    MoveTo(list_width as u16, (row + 2) as u16),
+                    Print("│ ".dark_grey()),
+                    Print(line)
+                )?;
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ignore::Type, picker::filter};
+
+    #[test]
+    fn filters_names_fuzzily() {
+        let names = vec![
+            Type::Normal("intellij".into()),
+            Type::Alias("rust".into()),
+            Type::Normal("intellij+all".into()),
+            Type::Template("docs".into()),
+        ];
+
+        let result: Vec<_> = filter(&names, "intl")
+            .into_iter()
+            .map(|index| names[index].inner())
+            .collect();
+        assert_eq!(result, vec!["intellij", "intellij+all"])
+    }
+}
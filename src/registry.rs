@@ -0,0 +1,88 @@
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The name of the manifest at the root of a registry, it uses the same format
+/// as the project config.
+pub const MANIFEST: &str = "registry.toml";
+
+/// A shared collection of aliases and templates, either a git repository that
+/// is cloned into the cache or a local directory.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Registry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl Registry {
+    /// The directory containing the manifest, for git repositories this is
+    /// their clone in the cache.
+    pub fn dir(&self, name: &str, cache_dir: &Path) -> Result<PathBuf> {
+        match (&self.git, &self.path) {
+            (Some(_), None) => Ok(cache_dir.join("registries").join(name)),
+            (None, Some(path)) => Ok(path.clone()),
+            _ => bail!("Registry {} must have exactly one of `git` or `path`", name),
+        }
+    }
+
+    /// Clones or pulls the repository of a git registry into the cache, local
+    /// registries are always up to date.
+    pub fn update(&self, name: &str, cache_dir: &Path) -> Result<()> {
+        let url = match &self.git {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+        let dir = self.dir(name, cache_dir)?;
+
+        let mut git = Command::new("git");
+        if dir.join(".git").exists() {
+            git.arg("-C")
+                .arg(&dir)
+                .args(["pull", "--ff-only", "--quiet"]);
+        } else {
+            git.args(["clone", "--depth", "1", "--quiet"]);
+            if let Some(branch) = &self.branch {
+                git.args(["--branch", branch]);
+            }
+            git.arg(url).arg(&dir);
+        }
+
+        let status = git
+            .status()
+            .context("Could not run git, is it installed?")?;
+        if !status.success() {
+            bail!("Could not update registry {} from {}", name, url);
+        }
+
+        eprintln!(
+            "{}: Updated registry {}",
+            "Info".bold().green(),
+            name.green()
+        );
+        Ok(())
+    }
+
+    /// Reads the manifest of the registry, returns `None` if a git registry
+    /// has not been fetched yet.
+    pub fn load(&self, name: &str, cache_dir: &Path) -> Result<Option<Config>> {
+        let manifest = self.dir(name, cache_dir)?.join(MANIFEST);
+        if !manifest.exists() {
+            if self.git.is_none() {
+                bail!("Registry {} has no {}", name, manifest.display());
+            }
+            return Ok(None);
+        }
+
+        Config::read_relative(&manifest).map(Some)
+    }
+}
@@ -0,0 +1,422 @@
+use crate::detector::DirEntry;
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ffi::OsString,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// How deep `Scanner` walks by default, the current directory is depth 1.
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+
+/// Directories that contain dependencies or build output rather than the
+/// project itself, these are never walked.
+pub const DEFAULT_SKIP: &[&str] = &[
+    ".git",
+    "node_modules",
+    "bower_components",
+    "target",
+    "vendor",
+    ".venv",
+    "venv",
+    "__pycache__",
+    "dist",
+];
+
+/// Files marking the root of a project, used to split a monorepo into its
+/// subprojects.
+pub const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "Gemfile",
+    "Package.swift",
+    "build.gradle",
+    "build.gradle.kts",
+    "build.sbt",
+    "composer.json",
+    "go.mod",
+    "mix.exs",
+    "package.json",
+    "pom.xml",
+    "pubspec.yaml",
+    "pyproject.toml",
+    "requirements.txt",
+    "setup.py",
+];
+
+/// A file or directory found while scanning, with its path relative to the
+/// directory the scan started in.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    full_path: PathBuf,
+    is_file: bool,
+    is_dir: bool,
+    /// Whether the contents are read from the git index instead of the
+    /// working tree
+    in_index: bool,
+}
+
+impl DirEntry for Entry {
+    fn relative_path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn file_name(&self) -> OsString {
+        self.path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_default()
+    }
+
+    fn extension(&self) -> Option<OsString> {
+        self.path.extension().map(OsString::from)
+    }
+
+    fn is_file(&self) -> bool {
+        self.is_file
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn read_to_string(&self) -> Option<String> {
+        if !self.in_index {
+            return std::fs::read_to_string(&self.full_path).ok();
+        }
+
+        let dir = self.full_path.parent()?;
+        let output = Command::new("git")
+            .arg("show")
+            .arg(format!(":./{}", self.file_name().to_string_lossy()))
+            .current_dir(dir)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+}
+
+impl Entry {
+    /// The same entry with its path relative to `dir`, `None` if it is not
+    /// inside `dir`.
+    fn relative_to(&self, dir: &Path) -> Option<Entry> {
+        match self.path.strip_prefix(dir) {
+            Ok(path) if !path.as_os_str().is_empty() => Some(Entry {
+                path: path.to_path_buf(),
+                ..self.clone()
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Splits the entries of a scan by subproject, the directories containing
+/// one of the `MANIFESTS`. Every entry belongs to the closest subproject it
+/// is in, with its path relative to it. Entries outside of any subproject
+/// are dropped.
+pub fn subprojects(entries: &[Entry]) -> BTreeMap<PathBuf, Vec<Entry>> {
+    let roots: BTreeSet<PathBuf> = entries
+        .iter()
+        .filter(|entry| entry.is_file && MANIFESTS.iter().any(|m| entry.file_name() == *m))
+        .filter_map(|entry| entry.path.parent().map(Path::to_path_buf))
+        .collect();
+
+    let mut subprojects: BTreeMap<PathBuf, Vec<Entry>> = BTreeMap::new();
+    for entry in entries {
+        let closest = roots
+            .iter()
+            .filter_map(|root| Some((root, entry.relative_to(root)?)))
+            .max_by_key(|(root, _)| root.components().count());
+
+        if let Some((root, entry)) = closest {
+            subprojects.entry(root.clone()).or_default().push(entry);
+        }
+    }
+
+    subprojects
+}
+
+/// Recursively walks a directory to find the files to run the detectors on.
+/// Vendored directories and directories ignored by git are included, so that
+/// they can be matched, but not walked. Ignored files are skipped.
+#[derive(Debug)]
+pub struct Scanner {
+    max_depth: usize,
+    skip: HashSet<OsString>,
+}
+
+impl Scanner {
+    pub fn new<S: AsRef<str>>(max_depth: usize, skip: &[S]) -> Self {
+        let skip = DEFAULT_SKIP
+            .iter()
+            .map(OsString::from)
+            .chain(skip.iter().map(|s| OsString::from(s.as_ref())))
+            .collect();
+
+        Scanner { max_depth, skip }
+    }
+
+    pub fn scan(&self, root: &Path) -> Result<Vec<Entry>> {
+        let ignored = ignored_paths(root);
+        let mut entries = Vec::new();
+        self.walk(root, Path::new(""), 1, &ignored, &mut entries)?;
+
+        Ok(entries)
+    }
+
+    fn walk(
+        &self,
+        root: &Path,
+        dir: &Path,
+        depth: usize,
+        ignored: &HashSet<PathBuf>,
+        entries: &mut Vec<Entry>,
+    ) -> Result<()> {
+        if depth > self.max_depth {
+            return Ok(());
+        }
+
+        for entry in read_dir(root.join(dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            let skipped = self.skip.contains(&entry.file_name()) || ignored.contains(&path);
+
+            if file_type.is_dir() && !skipped {
+                // Unreadable directories should not stop the detection
+                let _ = self.walk(root, &path, depth + 1, ignored, entries);
+            } else if file_type.is_file() && skipped {
+                continue;
+            }
+
+            entries.push(Entry {
+                full_path: entry.path(),
+                path,
+                is_file: file_type.is_file(),
+                is_dir: file_type.is_dir(),
+                in_index: false,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Like `scan`, but finds the files tracked in the git index instead of
+    /// walking the working tree, along with the directories containing them.
+    /// Their contents are read from the index as well.
+    pub fn tracked(&self, root: &Path) -> Result<Vec<Entry>> {
+        let output = Command::new("git")
+            .args(["ls-files", "--cached", "-z"])
+            .current_dir(root)
+            .output()
+            .context("Could not run git, is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "Could not list tracked files, is {} in a git repository?",
+                root.display()
+            );
+        }
+
+        let mut dirs = BTreeSet::new();
+        let mut entries = Vec::new();
+        for path in output.stdout.split(|b| *b == 0) {
+            if path.is_empty() {
+                continue;
+            }
+
+            let path = PathBuf::from(String::from_utf8_lossy(path).as_ref());
+            let depth = path.components().count();
+            let mut parents: Vec<&Path> = path
+                .ancestors()
+                .skip(1)
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .collect();
+            parents.reverse();
+
+            // A directory is listed at its own depth but not walked, just
+            // like a skipped one
+            let walked = parents.iter().take_while(|parent| {
+                parent.components().count() < self.max_depth
+                    && !parent
+                        .file_name()
+                        .is_some_and(|name| self.skip.contains(name))
+            });
+            let listed = walked.count() + 1;
+            dirs.extend(parents.into_iter().take(listed).map(Path::to_path_buf));
+
+            let skipped = path
+                .file_name()
+                .is_some_and(|name| self.skip.contains(name));
+            if depth <= listed && depth <= self.max_depth && !skipped {
+                entries.push(Entry {
+                    full_path: root.join(&path),
+                    path,
+                    is_file: true,
+                    is_dir: false,
+                    in_index: true,
+                });
+            }
+        }
+
+        entries.extend(dirs.into_iter().map(|path| Entry {
+            full_path: root.join(&path),
+            path,
+            is_file: false,
+            is_dir: true,
+            in_index: true,
+        }));
+
+        Ok(entries)
+    }
+}
+
+/// Asks git for the untracked files and directories in `root` that are
+/// ignored, returns an empty set if `root` is not in a git repository.
+fn ignored_paths(root: &Path) -> HashSet<PathBuf> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "--others",
+            "--ignored",
+            "--exclude-standard",
+            "--directory",
+            "-z",
+        ])
+        .current_dir(root)
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => output
+            .stdout
+            .split(|b| *b == 0)
+            .filter(|path| !path.is_empty())
+            .map(|path| {
+                let path = String::from_utf8_lossy(path);
+                PathBuf::from(path.trim_end_matches('/'))
+            })
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        detector::DirEntry,
+        scanner::{subprojects, Scanner},
+    };
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, File},
+        process,
+    };
+
+    #[test]
+    fn scans_subdirectories_and_skips_vendored() {
+        let root = temp_dir().join(format!("git-ignore-scanner-{}", process::id()));
+        for dir in ["backend", "frontend/src", "frontend/node_modules/dep"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "backend/Cargo.toml",
+            "frontend/package.json",
+            "frontend/node_modules/dep/package.json",
+        ] {
+            File::create(root.join(file)).unwrap();
+        }
+
+        let entries = Scanner::new::<&str>(2, &[]).scan(&root).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        let mut files: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.is_file())
+            .map(|entry| entry.file_name())
+            .collect();
+        files.sort_unstable();
+        assert_eq!(files, vec!["Cargo.toml", "package.json"])
+    }
+
+    #[test]
+    fn lists_tracked_files_and_their_directories() {
+        let root = temp_dir().join(format!("git-ignore-tracked-{}", process::id()));
+        create_dir_all(root.join("web")).unwrap();
+        for file in ["web/package.json", "web/.next", "Cargo.toml"] {
+            File::create(root.join(file)).unwrap();
+        }
+        let git = |args: &[&str]| {
+            process::Command::new("git")
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "web/package.json"]);
+
+        let entries = Scanner::new::<&str>(2, &[]).tracked(&root).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        let mut paths: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.relative_path(), entry.is_dir()))
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            vec![("web".into(), true), ("web/package.json".into(), false)]
+        )
+    }
+
+    #[test]
+    fn splits_entries_by_subproject() {
+        let root = temp_dir().join(format!("git-ignore-subprojects-{}", process::id()));
+        for dir in ["docs", "web/src", "web/api"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "package.json",
+            "docs/index.md",
+            "web/package.json",
+            "web/src/app.tsx",
+            "web/api/requirements.txt",
+        ] {
+            File::create(root.join(file)).unwrap();
+        }
+
+        let entries = Scanner::new::<&str>(3, &[]).scan(&root).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        let result: Vec<_> = subprojects(&entries)
+            .into_iter()
+            .map(|(root, entries)| {
+                let mut files: Vec<_> = entries
+                    .iter()
+                    .filter(|entry| entry.is_file())
+                    .map(|entry| entry.relative_path())
+                    .collect();
+                files.sort_unstable();
+                (root, files)
+            })
+            .collect();
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "".into(),
+                    vec!["docs/index.md".into(), "package.json".into()]
+                ),
+                (
+                    "web".into(),
+                    vec!["package.json".into(), "src/app.tsx".into()]
+                ),
+                ("web/api".into(), vec!["requirements.txt".into()]),
+            ]
+        )
+    }
+}
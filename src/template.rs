@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Changes applied on top of a template from gitignore.io, removing lines
+/// that should not be ignored and appending extra lines or negations.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    #[serde(default)]
+    pub remove: Vec<String>,
+    #[serde(default)]
+    pub append: Vec<String>,
+}
+
+impl Patch {
+    pub fn apply(&self, contents: &str) -> String {
+        let mut result: String = contents
+            .lines()
+            .filter(|line| {
+                !self
+                    .remove
+                    .iter()
+                    .any(|remove| remove.trim() == line.trim())
+            })
+            .flat_map(|line| [line, "\n"])
+            .collect();
+
+        for line in &self.append {
+            result.push_str(line);
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+/// Parses a `#!include name...` line in a custom template, returning the
+/// names of the templates, aliases or normal templates it includes.
+pub fn include_directive(line: &str) -> Option<Vec<&str>> {
+    directive(line, "#!include").map(|rest| rest.split_whitespace().collect())
+}
+
+/// Parses a `#!default key=value` line in a custom template, returning the
+/// name of the variable and its default value.
+pub fn default_directive(line: &str) -> Option<(&str, &str)> {
+    directive(line, "#!default")
+        .and_then(|rest| rest.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Returns true if the line is a directive and should not be part of the
+/// rendered output.
+pub fn is_directive(line: &str) -> bool {
+    include_directive(line).is_some() || default_directive(line).is_some()
+}
+
+/// Replaces every `{{name}}` placeholder in `line` with its value from
+/// `variables`, returning the result and the names of any placeholders
+/// without a value. Unresolved placeholders are left as is.
+pub fn substitute(line: &str, variables: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(line.len());
+    let mut unresolved = Vec::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find("{{") {
+        let (before, after) = rest.split_at(start);
+        result.push_str(before);
+
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                rest = after;
+                break;
+            }
+        };
+
+        let name = after[2..end].trim();
+        match variables.get(name) {
+            Some(value) if is_variable_name(name) => result.push_str(value),
+            _ => {
+                if is_variable_name(name) {
+                    unresolved.push(name.to_string());
+                }
+                result.push_str(&after[..end + 2]);
+            }
+        }
+        rest = &after[end + 2..];
+    }
+
+    result.push_str(rest);
+    (result, unresolved)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.trim()
+        .strip_prefix(name)
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{default_directive, include_directive, substitute, Patch};
+    use std::collections::HashMap;
+
+    #[test]
+    fn parses_include_directive() {
+        let result = include_directive("#!include python jupyternotebooks");
+        assert_eq!(result, Some(vec!["python", "jupyternotebooks"]))
+    }
+
+    #[test]
+    fn ignores_similar_comments() {
+        assert_eq!(include_directive("#!included"), None);
+        assert_eq!(include_directive("# include python"), None);
+    }
+
+    #[test]
+    fn parses_default_directive() {
+        let result = default_directive("#!default build_dir = build");
+        assert_eq!(result, Some(("build_dir", "build")))
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let variables = HashMap::from([("build_dir".to_string(), "out".to_string())]);
+        let result = substitute("/{{build_dir}}/\n{{ build_dir }}.log", &variables);
+        assert_eq!(result, ("/out/\nout.log".to_string(), vec![]))
+    }
+
+    #[test]
+    fn reports_unresolved_variables() {
+        let result = substitute("{{project}}/{{build_dir}}", &HashMap::new());
+        assert_eq!(
+            result,
+            (
+                "{{project}}/{{build_dir}}".to_string(),
+                vec!["project".to_string(), "build_dir".to_string()]
+            )
+        )
+    }
+
+    #[test]
+    fn applies_patch() {
+        let patch = Patch {
+            remove: vec![".idea/".to_string()],
+            append: vec!["!.idea/runConfigurations".to_string()],
+        };
+        let result = patch.apply("\n### JetBrains ###\n.idea/\n*.iml\n");
+        assert_eq!(
+            result,
+            "\n### JetBrains ###\n*.iml\n!.idea/runConfigurations\n"
+        )
+    }
+}
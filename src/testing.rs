@@ -0,0 +1,56 @@
+use crate::config::Config;
+use std::{
+    env::temp_dir,
+    fs::{create_dir_all, remove_dir_all, write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A fresh directory for a test with an empty `templates` directory, removed
+/// when dropped, even when the test panics.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = temp_dir().join(format!(
+            "git-ignore-{}-{}-{}",
+            name,
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        create_dir_all(path.join("templates")).unwrap();
+
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes `contents` to `file` in the directory, creating its parents.
+    pub fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let file = self.path.join(file);
+        create_dir_all(file.parent().unwrap()).unwrap();
+        write(&file, contents).unwrap();
+        file
+    }
+
+    /// An empty config stored in `config.toml`, reading its templates from
+    /// the `templates` directory.
+    pub fn config(&self) -> Config {
+        Config {
+            path: self.path.join("config.toml"),
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}